@@ -0,0 +1,711 @@
+use crate::challenge::{self, ChallengeStatus};
+use crate::check::{self, Finding};
+use crate::error::Error;
+use crate::model::{
+    Activity, Category, CategoryLookup, Challenge, ChallengeTarget, Config, Plan, PlanSchedule,
+    StorageBackend,
+};
+use crate::remind::{self, GoalProgress};
+use crate::report::{self, DayReport, PlanReport, RangeReport};
+use crate::storage::{self, Backup, Storage};
+use chrono::{Date, DateTime, Local};
+use std::path::Path;
+
+/// Handle for working with the data of one naday installation. All results are returned as data;
+/// only unreadable lines skipped while reading the text files are reported on stderr.
+pub struct Naday {
+    config: Config,
+    storage: Box<dyn Storage>,
+}
+
+impl Naday {
+    /// Open the storage with exactly the given config
+    pub fn open(config: Config) -> Result<Naday, Error> {
+        let storage = storage::open(&config)?;
+        if config.daily_backup {
+            storage::create_daily_backup(&config)?;
+        }
+        Ok(Naday { config, storage })
+    }
+
+    /// Open the given data directory, applying the settings of its config file
+    pub fn open_data_dir<S: AsRef<str>>(data_dir: S) -> Result<Naday, Error> {
+        let mut config = Config::new(data_dir);
+        storage::read_config(&mut config)?;
+        Naday::open(config)
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub(crate) fn storage(&self) -> &dyn Storage {
+        self.storage.as_ref()
+    }
+
+    //
+    // Activities -------------------------------
+    //
+
+    /// Log the given number of repetitions for a category, given by its name or alias
+    pub fn log(&self, repetitions: u32, category: &str) -> Result<Activity, Error> {
+        self.log_at(repetitions, category, Local::now())
+    }
+
+    /// Log the given number of repetitions with an explicit timestamp
+    pub fn log_at(
+        &self,
+        repetitions: u32,
+        category: &str,
+        timestamp: DateTime<Local>,
+    ) -> Result<Activity, Error> {
+        if repetitions == 0 {
+            return Err(Error::validation(
+                "The number of repetitions must be at least 1",
+            ));
+        }
+
+        let category = self.find_category(category)?;
+        let mut activity = Activity::new(repetitions, &category.name);
+        activity.timestamp = timestamp;
+
+        self.storage.store(&activity)?;
+        Ok(activity)
+    }
+
+    /// All activities on the given range of days (both ends inclusive), sorted by timestamp
+    pub fn activities(
+        &self,
+        start: &Date<Local>,
+        end: &Date<Local>,
+    ) -> Result<Vec<Activity>, Error> {
+        validate_range(start, end)?;
+        let mut activities = self.storage.read_days(start, end)?;
+        activities.sort_by_key(|activity| activity.timestamp);
+        Ok(activities)
+    }
+
+    /// Check the stored data for problems, optionally repairing what can be repaired. The data
+    /// directory is backed up before repairing.
+    pub fn check(&self, fix: bool) -> Result<Vec<Finding>, Error> {
+        if fix {
+            storage::create_backup(&self.config, "check")?;
+            storage::upgrade_data_dir(&self.config)?;
+        }
+        check::run(self.storage(), &self.config, fix)
+    }
+
+    /// Upgrade all files in the data directory to the current format versions. Files are upgraded
+    /// in memory when reading, and in place before changing data, so this is never required.
+    ///
+    /// # Returns
+    /// The number of upgraded files
+    pub fn upgrade(&self) -> Result<usize, Error> {
+        storage::upgrade_data_dir(&self.config)
+    }
+
+    /// Move all categories and activities to another storage backend and switch to it. The data of
+    /// the old backend is removed afterwards, the backup made before keeps a copy of it. Data with
+    /// entries that would be skipped when copying is not migrated at all.
+    ///
+    /// # Returns
+    /// The number of migrated categories and activities
+    pub fn migrate(self, to: StorageBackend) -> Result<(usize, usize), Error> {
+        if to == self.config.storage_backend {
+            return Err(Error::conflict(format!(
+                "Data is already stored in the {} backend",
+                to
+            )));
+        }
+
+        if self.config.storage_backend == StorageBackend::Text {
+            let problems = storage::check_files(&self.config, false)?.len();
+            if problems > 0 {
+                return Err(Error::problems_remaining(format!(
+                    "{} problems found that would lose data when migrating - run 'naday check' and repair them first",
+                    problems
+                )));
+            }
+        }
+
+        storage::create_backup(&self.config, "migrate")?;
+
+        let mut target_config = self.config.clone();
+        target_config.storage_backend = to;
+        let target = storage::open(&target_config)?;
+
+        if !target.read_all()?.is_empty() {
+            return Err(Error::conflict(format!(
+                "The {} backend in {} already contains activities - please move them away first",
+                to, &self.config.data_dir
+            )));
+        }
+
+        let copied = storage::copy_all(self.storage(), target.as_ref())?;
+        storage::write_setting(&self.config, "storage", &to.to_string())?;
+
+        // the old backend must be closed before its data can be removed
+        drop(self.storage);
+        storage::remove_data(&self.config)?;
+
+        Ok(copied)
+    }
+
+    //
+    // Reports ---------------------------------
+    //
+
+    /// All sets logged on the given day, with the targets of the training plan or the daily goals
+    pub fn day_report(&self, day: &Date<Local>) -> Result<DayReport, Error> {
+        let plan = storage::read_plan(&self.config)?;
+        report::day(day, self.storage(), plan.as_ref(), &self.config)
+    }
+
+    /// Daily totals for the given range of days (both ends inclusive), optionally focusing on a
+    /// category given by its name or alias
+    pub fn range_report(
+        &self,
+        start: &Date<Local>,
+        end: &Date<Local>,
+        category: Option<&str>,
+    ) -> Result<RangeReport, Error> {
+        validate_range(start, end)?;
+        report::range(start, end, category, self.storage(), &self.config)
+    }
+
+    /// Progress of the day containing `at` towards the daily goals, compared to the configured pace
+    pub fn goal_progress(&self, at: &DateTime<Local>) -> Result<Vec<GoalProgress>, Error> {
+        remind::progress(self.storage(), &self.config, at)
+    }
+
+    //
+    // Categories ------------------------------
+    //
+
+    /// All categories (including archived ones) in the order they were defined
+    pub fn categories(&self) -> Result<Vec<Category>, Error> {
+        Ok(self
+            .storage
+            .read_categories()?
+            .iter()
+            .map(|category| category.as_ref().clone())
+            .collect())
+    }
+
+    /// Find an active category by its name or alias, using the configured matching mode
+    pub fn find_category(&self, name_or_alias: &str) -> Result<Category, Error> {
+        let categories = self.storage.read_categories()?;
+        let category = categories.resolve(name_or_alias, self.config.category_matching)?;
+        Ok(category.as_ref().clone())
+    }
+
+    /// Add a new category. Its name and aliases must not be in use yet.
+    pub fn add_category(&self, category: Category) -> Result<(), Error> {
+        validate_category(&category)?;
+
+        // checks for collisions with existing names and aliases
+        let mut categories = self.storage.read_categories()?;
+        categories.add(category.clone())?;
+
+        self.storage.add_category(&category)
+    }
+
+    /// Replace the category with the same name (e.g. to change its weight or archive it)
+    pub fn update_category(&self, category: Category) -> Result<(), Error> {
+        validate_category(&category)?;
+
+        let mut categories = self.categories()?;
+        match categories
+            .iter_mut()
+            .find(|c| c.name.eq_ignore_ascii_case(&category.name))
+        {
+            Some(existing) => *existing = category,
+            None => {
+                return Err(Error::UnknownCategory {
+                    name: category.name,
+                    suggestion: None,
+                })
+            }
+        }
+
+        // checks for collisions with the other names and aliases
+        let mut lookup = CategoryLookup::new();
+        for category in &categories {
+            lookup.add(category.clone())?;
+        }
+
+        storage::create_backup(&self.config, "update-category")?;
+        self.storage.write_categories(&categories)
+    }
+
+    //
+    // Challenges ------------------------------
+    //
+
+    /// All challenges in the order they were defined
+    pub fn challenges(&self) -> Result<Vec<Challenge>, Error> {
+        storage::read_challenges(&self.config)
+    }
+
+    /// Find a challenge by its (case insensitive) name
+    pub fn find_challenge(&self, name: &str) -> Result<Challenge, Error> {
+        self.challenges()?
+            .into_iter()
+            .find(|challenge| challenge.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| Error::validation(format!("Challenge '{}' is not known", name)))
+    }
+
+    /// Add a new challenge. Its category may be given by name or alias, its name must not be in
+    /// use yet.
+    ///
+    /// # Returns
+    /// The challenge as stored, with the full category name
+    pub fn add_challenge(&self, challenge: Challenge) -> Result<Challenge, Error> {
+        if !Category::is_valid_name(&challenge.name) {
+            return Err(Error::validation(format!(
+                "'{}' is not a valid challenge name - use letters, digits and '_', starting with a letter",
+                challenge.name
+            )));
+        }
+        if challenge.end < challenge.start {
+            return Err(Error::validation(
+                "The last day of the challenge is before its first day",
+            ));
+        }
+        let (ChallengeTarget::Total(reps) | ChallengeTarget::Daily(reps)) = challenge.target;
+        if reps == 0 {
+            return Err(Error::validation(
+                "The target of the challenge must be at least 1 repetition",
+            ));
+        }
+        if self.find_challenge(&challenge.name).is_ok() {
+            return Err(Error::conflict(format!(
+                "Challenge '{}' already exists",
+                challenge.name
+            )));
+        }
+
+        let challenge = Challenge {
+            category: self.find_category(&challenge.category)?.name,
+            ..challenge
+        };
+        storage::add_challenge(&self.config, &challenge)?;
+        Ok(challenge)
+    }
+
+    /// Progress of the given challenge up to the given day
+    pub fn challenge_status(
+        &self,
+        challenge: &Challenge,
+        day: &Date<Local>,
+    ) -> Result<ChallengeStatus, Error> {
+        challenge::status(challenge, day, self.storage(), &self.config)
+    }
+
+    //
+    // Training plan ---------------------------
+    //
+
+    /// The loaded training plan, if any
+    pub fn plan(&self) -> Result<Option<Plan>, Error> {
+        storage::read_plan(&self.config)
+    }
+
+    /// Read a training plan from the given file, without loading it
+    pub fn read_plan_file(&self, path: &Path) -> Result<Plan, Error> {
+        storage::read_plan_file(path)
+    }
+
+    /// Replace the training plan with the given one. Its categories may be given by name or alias.
+    ///
+    /// # Returns
+    /// The plan as stored, with the full category names
+    pub fn load_plan(&self, plan: Plan) -> Result<Plan, Error> {
+        let mut schedules: Vec<PlanSchedule> = Vec::new();
+        for schedule in plan.schedules {
+            let category = self.find_category(&schedule.category)?.name;
+            if schedules.iter().any(|s| s.category == category) {
+                return Err(Error::validation(format!(
+                    "The plan contains more than one schedule for '{}'",
+                    category
+                )));
+            }
+            schedules.push(PlanSchedule {
+                category,
+                ..schedule
+            });
+        }
+
+        let plan = Plan { schedules, ..plan };
+        storage::create_backup(&self.config, "plan")?;
+        storage::write_plan(&self.config, Some(&plan))?;
+        Ok(plan)
+    }
+
+    /// Remove the training plan, so the daily goals apply again
+    pub fn clear_plan(&self) -> Result<(), Error> {
+        storage::create_backup(&self.config, "plan")?;
+        storage::write_plan(&self.config, None)
+    }
+
+    /// Adherence to the given training plan up to the given day
+    pub fn plan_report(&self, plan: &Plan, day: &Date<Local>) -> Result<PlanReport, Error> {
+        report::plan_adherence(plan, day, self.storage(), &self.config)
+    }
+
+    //
+    // Backups ---------------------------------
+    //
+
+    /// All backups of the data directory, oldest first
+    pub fn backups(&self) -> Result<Vec<Backup>, Error> {
+        storage::list_backups(&self.config)
+    }
+
+    /// Back up the data directory. Manual backups are never deleted automatically.
+    pub fn create_backup(&self) -> Result<Backup, Error> {
+        storage::create_backup(&self.config, "manual")
+    }
+
+    /// Replace all data with the backup with the given id. The current state is backed up before.
+    ///
+    /// # Returns
+    /// The backup of the state before restoring
+    pub fn restore_backup(&self, id: &str) -> Result<Backup, Error> {
+        storage::restore_backup(&self.config, id)
+    }
+}
+
+//
+// Helpers ----------------------------------
+//
+
+fn validate_range(start: &Date<Local>, end: &Date<Local>) -> Result<(), Error> {
+    if end < start {
+        return Err(Error::validation(format!(
+            "The end {} is before the start {}",
+            end.naive_local(),
+            start.naive_local()
+        )));
+    }
+
+    Ok(())
+}
+
+fn validate_category(category: &Category) -> Result<(), Error> {
+    for name in std::iter::once(&category.name).chain(category.aliases.iter()) {
+        if !Category::is_valid_name(name) {
+            return Err(Error::validation(format!(
+                "'{}' is not a valid name or alias - use letters, digits and '_', starting with a letter",
+                name
+            )));
+        }
+    }
+
+    let weights =
+        std::iter::once(category.weight).chain(category.weight_changes.iter().map(|(_, w)| *w));
+    for weight in weights {
+        if !(weight.is_finite() && weight > 0.0) {
+            return Err(Error::validation(format!(
+                "Invalid weight {} for category '{}' - must be greater than 0",
+                weight, category.name
+            )));
+        }
+    }
+
+    if category.goal == Some(0) {
+        return Err(Error::validation(format!(
+            "Invalid goal 0 for category '{}' - must be at least 1 repetition",
+            category.name
+        )));
+    }
+
+    Ok(())
+}
+
+//
+// Tests ---------------------------------
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::check::Problem;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    #[test]
+    fn log_and_report() {
+        let tmp = TempDir::new().unwrap();
+        let naday = Naday::open(Config::new(tmp.path().to_str().unwrap())).unwrap();
+
+        let day = Local.ymd(2021, 3, 14);
+        let activity = naday.log_at(20, "pu", day.and_hms(9, 0, 0)).unwrap();
+        assert_eq!("Pushups", activity.category);
+        naday.log_at(10, "Burp", day.and_hms(18, 0, 0)).unwrap();
+        naday.log_at(5, "pu", day.and_hms(20, 0, 0)).unwrap();
+        naday.log_at(7, "pu", day.succ().and_hms(8, 0, 0)).unwrap();
+
+        assert_eq!(3, naday.activities(&day, &day).unwrap().len());
+        assert_eq!(7, exit_code(naday.log(0, "pu")));
+        assert_eq!(3, exit_code(naday.log(5, "Lunges")));
+
+        let report = naday.day_report(&day).unwrap();
+        assert_eq!(vec![20, 5], report.sets_by_category["Pushups"]);
+        assert_eq!(25, report.reps("Pushups"));
+        assert_eq!(25 + 15, report.weighted_total);
+
+        assert_eq!(7, exit_code(naday.range_report(&day.succ(), &day, None)));
+        assert_eq!(7, exit_code(naday.activities(&day.succ(), &day)));
+
+        let report = naday.range_report(&day, &day.succ(), Some("push")).unwrap();
+        assert_eq!(Some("Pushups".to_string()), report.category);
+        assert_eq!(
+            vec![(25, 40), (7, 7)],
+            report
+                .days
+                .iter()
+                .map(|d| (d.reps("Pushups"), d.weighted_total))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn upgrade_on_write() {
+        let tmp = TempDir::new().unwrap();
+        let march = tmp.path().join("2021-03.txt");
+        let v1 = "naday activities v1\n2021-03-01 10:00:00;10;Pushups\n";
+        std::fs::write(&march, v1).unwrap();
+        let naday = Naday::open(Config::new(tmp.path().to_str().unwrap())).unwrap();
+
+        // reading upgrades in memory only
+        let day = Local.ymd(2021, 3, 1);
+        assert_eq!(10, naday.day_report(&day).unwrap().reps("Pushups"));
+        assert_eq!(v1, std::fs::read_to_string(&march).unwrap());
+        assert!(naday.backups().unwrap().is_empty());
+
+        naday.log_at(5, "pu", day.and_hms(12, 0, 0)).unwrap();
+        let contents = std::fs::read_to_string(&march).unwrap();
+        assert!(
+            contents.starts_with("naday activities v2\n"),
+            "{}",
+            contents
+        );
+        assert_eq!(15, naday.day_report(&day).unwrap().reps("Pushups"));
+        let backups = naday.backups().unwrap();
+        assert_eq!(1, backups.len());
+        assert_eq!("upgrade", backups[0].reason);
+        assert_eq!(0, naday.upgrade().unwrap());
+    }
+
+    #[test]
+    fn manage_categories() {
+        let tmp = TempDir::new().unwrap();
+        let naday = Naday::open(Config::new(tmp.path().to_str().unwrap())).unwrap();
+
+        naday
+            .add_category(Category::new("Lunges", 1.0, vec!["lu"]))
+            .unwrap();
+        assert_eq!("Lunges", naday.find_category("lu").unwrap().name);
+
+        let error = naday
+            .add_category(Category::new("Squats", 1.0, vec!["lu"]))
+            .unwrap_err();
+        assert_eq!(6, error.exit_code());
+        let error = naday
+            .add_category(Category::new("Jumping Jacks", 1.0, vec!["jj"]))
+            .unwrap_err();
+        assert_eq!(7, error.exit_code());
+        let mut squats = Category::new("Squats", 1.0, vec!["sq"]);
+        squats.goal = Some(0);
+        assert_eq!(7, exit_code(naday.add_category(squats)));
+
+        let mut lunges = naday.find_category("Lunges").unwrap();
+        lunges.weight = 0.5;
+        lunges.archived = true;
+        naday.update_category(lunges).unwrap();
+
+        let lunges = naday
+            .categories()
+            .unwrap()
+            .into_iter()
+            .find(|c| c.name == "Lunges")
+            .unwrap();
+        assert!(lunges.archived);
+        assert_eq!(0.5, lunges.weight);
+        assert!(naday.find_category("lu").is_err());
+
+        let backups = naday.backups().unwrap();
+        assert_eq!(1, backups.len());
+        assert_eq!("update-category", backups[0].reason);
+    }
+
+    #[test]
+    fn challenges() {
+        let tmp = TempDir::new().unwrap();
+        let naday = Naday::open(Config::new(tmp.path().to_str().unwrap())).unwrap();
+
+        let day = Local.ymd(2021, 3, 14);
+        let challenge = Challenge {
+            name: "march".to_string(),
+            category: "pu".to_string(),
+            start: day.naive_local(),
+            end: day.succ().naive_local(),
+            target: ChallengeTarget::Daily(10),
+        };
+        let added = naday.add_challenge(challenge.clone()).unwrap();
+        assert_eq!("Pushups", added.category);
+        assert_eq!(vec![added.clone()], naday.challenges().unwrap());
+        assert_eq!(added, naday.find_challenge("MARCH").unwrap());
+        assert_eq!(7, exit_code(naday.find_challenge("april")));
+
+        naday.log_at(12, "pu", day.and_hms(9, 0, 0)).unwrap();
+        let status = naday.challenge_status(&added, &day).unwrap();
+        assert_eq!(12, status.reps);
+        assert_eq!(2, status.days_left);
+
+        assert_eq!(6, exit_code(naday.add_challenge(challenge.clone())));
+        let invalid = vec![
+            Challenge {
+                name: "no spaces".to_string(),
+                ..challenge.clone()
+            },
+            Challenge {
+                name: "zero".to_string(),
+                target: ChallengeTarget::Total(0),
+                ..challenge.clone()
+            },
+            Challenge {
+                name: "backwards".to_string(),
+                end: day.pred().naive_local(),
+                ..challenge.clone()
+            },
+        ];
+        for challenge in invalid {
+            assert_eq!(7, exit_code(naday.add_challenge(challenge)));
+        }
+        let unknown = Challenge {
+            name: "lunges".to_string(),
+            category: "Lunges".to_string(),
+            ..challenge
+        };
+        assert_eq!(3, exit_code(naday.add_challenge(unknown)));
+    }
+
+    #[test]
+    fn training_plan() {
+        let tmp = TempDir::new().unwrap();
+        let naday = Naday::open(Config::new(tmp.path().to_str().unwrap())).unwrap();
+        assert_eq!(None, naday.plan().unwrap());
+
+        let day = Local.ymd(2021, 3, 14);
+        let plan = Plan {
+            start: day.pred().naive_local(),
+            schedules: vec![PlanSchedule {
+                category: "pu".to_string(),
+                schedule: crate::model::Schedule::Days(vec![10, 20]),
+            }],
+        };
+        let stored = naday.load_plan(plan.clone()).unwrap();
+        assert_eq!("Pushups", stored.schedules[0].category);
+        assert_eq!(Some(stored.clone()), naday.plan().unwrap());
+        assert_eq!("plan", naday.backups().unwrap()[0].reason);
+
+        naday.log_at(15, "pu", day.and_hms(9, 0, 0)).unwrap();
+        let report = naday.day_report(&day).unwrap();
+        assert_eq!(Some(&20), report.targets.get("Pushups"));
+        let report = naday.plan_report(&stored, &day).unwrap();
+        assert_eq!(
+            vec![(0, 10), (15, 20)],
+            report
+                .days
+                .iter()
+                .map(|d| (d.entries[0].reps, d.entries[0].target))
+                .collect::<Vec<_>>()
+        );
+
+        let mut twice = plan.clone();
+        twice.schedules.push(PlanSchedule {
+            category: "Pushups".to_string(),
+            ..plan.schedules[0].clone()
+        });
+        assert_eq!(7, exit_code(naday.load_plan(twice)));
+        let mut unknown = plan;
+        unknown.schedules[0].category = "Lunges".to_string();
+        assert_eq!(3, exit_code(naday.load_plan(unknown)));
+
+        naday.clear_plan().unwrap();
+        assert_eq!(None, naday.plan().unwrap());
+        assert_eq!(None, naday.day_report(&day).unwrap().targets.get("Pushups"));
+    }
+
+    #[test]
+    fn migrate_refuses_problems() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::new(tmp.path().to_str().unwrap());
+        let naday = Naday::open(config.clone()).unwrap();
+        naday
+            .log_at(20, "pu", Local.ymd(2021, 3, 14).and_hms(9, 0, 0))
+            .unwrap();
+        let march = tmp.path().join("2021-03.txt");
+        let mut contents = std::fs::read_to_string(&march).unwrap();
+        contents.push_str("2021-03-14 10:00;12;Pushups\n");
+        std::fs::write(&march, &contents).unwrap();
+
+        assert_eq!(9, exit_code(naday.migrate(StorageBackend::Sqlite)));
+        assert_eq!(contents, std::fs::read_to_string(&march).unwrap());
+        let naday = Naday::open(config).unwrap();
+        assert!(naday.backups().unwrap().is_empty());
+
+        // duplicate category definitions would be lost too
+        let categories = tmp.path().join("categories.txt");
+        std::fs::write(&march, "naday activities v2\n").unwrap();
+        let mut contents = std::fs::read_to_string(&categories).unwrap();
+        contents.push_str("pushups;2\n");
+        std::fs::write(&categories, &contents).unwrap();
+        let findings = naday.check(false).unwrap();
+        assert_eq!(1, findings.len());
+        assert!(
+            matches!(&findings[0].problem, Problem::DuplicateCategory { name, .. } if name == "pushups")
+        );
+        assert_eq!(9, exit_code(naday.migrate(StorageBackend::Sqlite)));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn migrate_round_trip() {
+        let tmp = TempDir::new().unwrap();
+        let data_dir = tmp.path().to_str().unwrap();
+        let day = Local.ymd(2021, 3, 14);
+
+        let naday = Naday::open_data_dir(data_dir).unwrap();
+        naday.log_at(20, "pu", day.and_hms(9, 0, 0)).unwrap();
+        assert_eq!((6, 1), naday.migrate(StorageBackend::Sqlite).unwrap());
+        assert!(!tmp.path().join("2021-03.txt").exists());
+        assert!(!tmp.path().join("categories.txt").exists());
+
+        let naday = Naday::open_data_dir(data_dir).unwrap();
+        assert_eq!(StorageBackend::Sqlite, naday.config().storage_backend);
+        naday.log_at(5, "pu", day.and_hms(18, 0, 0)).unwrap();
+        assert_eq!((6, 2), naday.migrate(StorageBackend::Text).unwrap());
+        assert!(!tmp.path().join("naday.sqlite").exists());
+
+        let naday = Naday::open_data_dir(data_dir).unwrap();
+        assert_eq!(StorageBackend::Text, naday.config().storage_backend);
+        assert_eq!(25, naday.day_report(&day).unwrap().reps("Pushups"));
+        // the backup keeps the data of the old backend
+        let backup = &naday.backups().unwrap()[0];
+        assert_eq!("migrate", backup.reason);
+        let backup_dir = tmp.path().join("backups").join(&backup.id);
+        assert!(backup_dir.join("2021-03.txt").exists());
+
+        assert_eq!(
+            6,
+            exit_code(naday.migrate(StorageBackend::Text)),
+            "already in the text backend"
+        );
+    }
+
+    fn exit_code<T>(result: Result<T, Error>) -> i32 {
+        result.err().map(|e| e.exit_code()).unwrap_or(0)
+    }
+}
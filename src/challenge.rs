@@ -0,0 +1,380 @@
+use crate::error::Error;
+use crate::model::{Challenge, ChallengeTarget, Config};
+use crate::storage::Storage;
+use chrono::{Date, Datelike, Local, NaiveDate, TimeZone};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Repetitions of the challenge category on a single day of a challenge
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChallengeDay {
+    pub day: NaiveDate,
+    pub reps: u32,
+    /// Repetitions to reach on this day. For total targets, the share of an even distribution.
+    pub target: u32,
+}
+
+impl ChallengeDay {
+    pub fn passed(&self) -> bool {
+        self.reps >= self.target
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChallengeState {
+    Upcoming,
+    Running,
+    Passed,
+    Failed,
+}
+
+impl fmt::Display for ChallengeState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChallengeState::Upcoming => write!(f, "upcoming"),
+            ChallengeState::Running => write!(f, "running"),
+            ChallengeState::Passed => write!(f, "passed"),
+            ChallengeState::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+/// Progress of a challenge up to a given day
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChallengeStatus {
+    pub challenge: Challenge,
+    pub state: ChallengeState,
+    /// Repetitions on all days of the challenge so far
+    pub reps: u32,
+    /// All days of the challenge up to today
+    pub days: Vec<ChallengeDay>,
+    /// Remaining days of the challenge, including today
+    pub days_left: u32,
+    /// Repetitions needed on each remaining day (including today) to reach the target
+    pub required_daily: u32,
+}
+
+impl ChallengeStatus {
+    /// Progress in percent: Of the total target, or of the days passed for daily targets
+    pub fn percent(&self) -> u32 {
+        let (done, target) = match self.challenge.target {
+            ChallengeTarget::Total(total) => (self.reps, total),
+            ChallengeTarget::Daily(_) => (
+                self.days.iter().filter(|day| day.passed()).count() as u32,
+                self.challenge.days(),
+            ),
+        };
+
+        (u64::from(done) * 100)
+            .checked_div(u64::from(target))
+            .unwrap_or(100)
+            .min(100) as u32
+    }
+}
+
+/// Determine the progress of the given challenge on the given day
+pub fn status(
+    challenge: &Challenge,
+    today: &Date<Local>,
+    storage: &dyn Storage,
+    config: &Config,
+) -> Result<ChallengeStatus, Error> {
+    let today = today.naive_local();
+    let last = today.min(challenge.end);
+
+    let mut by_day: HashMap<NaiveDate, u32> = HashMap::new();
+    if last >= challenge.start {
+        for activity in storage.read_days(&local(challenge.start), &local(last))? {
+            let day = config.day_of(&activity.timestamp).naive_local();
+            if activity.category == challenge.category && day >= challenge.start && day <= last {
+                *by_day.entry(day).or_insert(0) += activity.reps;
+            }
+        }
+    }
+
+    let mut days = Vec::new();
+    let mut day = challenge.start;
+    while day <= last {
+        days.push(ChallengeDay {
+            day,
+            reps: by_day.get(&day).copied().unwrap_or(0),
+            target: challenge.daily_target(),
+        });
+        day = day.succ();
+    }
+
+    let reps: u32 = days.iter().map(|day| day.reps).sum();
+    let reps_before_today: u32 = days
+        .iter()
+        .filter(|day| day.day < today)
+        .map(|day| day.reps)
+        .sum();
+    let days_left = if today > challenge.end {
+        0
+    } else {
+        (challenge.end - today.max(challenge.start)).num_days() as u32 + 1
+    };
+
+    let (state, required_daily) = match challenge.target {
+        _ if today < challenge.start => (ChallengeState::Upcoming, challenge.daily_target()),
+        ChallengeTarget::Total(total) => {
+            let state = if reps >= total {
+                ChallengeState::Passed
+            } else if days_left == 0 {
+                ChallengeState::Failed
+            } else {
+                ChallengeState::Running
+            };
+            let remaining = total.saturating_sub(reps_before_today);
+            let required = remaining.div_ceil(days_left.max(1));
+            (
+                state,
+                if state == ChallengeState::Running {
+                    required
+                } else {
+                    0
+                },
+            )
+        }
+        ChallengeTarget::Daily(daily) => {
+            let missed = days.iter().any(|day| day.day < today && !day.passed());
+            let complete = days.len() as u32 == challenge.days();
+            let state = if missed {
+                ChallengeState::Failed
+            } else if complete && days.iter().all(|day| day.passed()) {
+                ChallengeState::Passed
+            } else {
+                ChallengeState::Running
+            };
+            (
+                state,
+                if state == ChallengeState::Running {
+                    daily
+                } else {
+                    0
+                },
+            )
+        }
+    };
+
+    Ok(ChallengeStatus {
+        challenge: challenge.clone(),
+        state,
+        reps,
+        days,
+        days_left,
+        required_daily,
+    })
+}
+
+/// Format a one line summary of the challenge
+pub fn format_summary(status: &ChallengeStatus, config: &Config) -> String {
+    let challenge = &status.challenge;
+    format!(
+        "{}: {} {} from {} to {} - {}, {}%",
+        challenge.name,
+        challenge.category,
+        challenge.target,
+        challenge.start.format(&config.date_format),
+        challenge.end.format(&config.date_format),
+        status.state,
+        status.percent()
+    )
+}
+
+/// Format the progress of the challenge, with the result of each day so far
+pub fn format_details(status: &ChallengeStatus, config: &Config) -> String {
+    let mut result = format_summary(status, config);
+    result.push('\n');
+
+    let progress = match status.challenge.target {
+        ChallengeTarget::Total(total) => format!("{} of {} reps", status.reps, total),
+        ChallengeTarget::Daily(_) => format!(
+            "{} of {} days passed, {} reps",
+            status.days.iter().filter(|day| day.passed()).count(),
+            status.challenge.days(),
+            status.reps
+        ),
+    };
+    result.push_str(&format!("  Progress: {}\n", progress));
+    if status.state == ChallengeState::Running || status.state == ChallengeState::Upcoming {
+        result.push_str(&format!(
+            "  Days left: {}, needed per day: {}\n",
+            status.days_left, status.required_daily
+        ));
+    }
+
+    // while the challenge runs, the last day is today
+    let today = status
+        .days
+        .last()
+        .filter(|_| status.days_left > 0)
+        .map(|day| day.day);
+    for day in &status.days {
+        let verdict = if day.passed() {
+            "passed"
+        } else if Some(day.day) == today {
+            "open"
+        } else {
+            "failed"
+        };
+        result.push_str(&format!(
+            "  {:3} {}: {:>5} of {:<5} {}\n",
+            day.day.weekday(),
+            day.day.format(&config.date_format),
+            day.reps,
+            day.target,
+            verdict
+        ));
+    }
+
+    result
+}
+
+//
+// Internals -----------------------------------
+//
+
+/// The given date in the local time zone, even if a DST change skips its midnight
+fn local(date: NaiveDate) -> Date<Local> {
+    Local
+        .from_local_date(&date)
+        .earliest()
+        .unwrap_or_else(|| Local.from_utc_date(&date))
+}
+
+//
+// Tests ---------------------------------------------------
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Activity, Category};
+    use crate::storage::memory::MemoryStorage;
+
+    #[test]
+    fn total_target() {
+        let storage = storage();
+        let config = Config::new("/nonexistent");
+        let challenge = pushups_challenge(ChallengeTarget::Total(100));
+
+        // 20 + 30 before the 5th, 10 on the 5th: 50 remaining for 3 days
+        let status = status(&challenge, &day(5), &storage, &config).unwrap();
+        assert_eq!(ChallengeState::Running, status.state);
+        assert_eq!(60, status.reps);
+        assert_eq!(3, status.days_left);
+        assert_eq!(17, status.required_daily);
+        assert_eq!(60, status.percent());
+        assert_eq!(
+            vec![(20, true), (0, false), (30, true), (10, false)],
+            days(&status)
+        );
+        assert_eq!(
+            "\
+pushups: Pushups 100 in total from 2021-03-02 to 2021-03-07 - running, 60%
+  Progress: 60 of 100 reps
+  Days left: 3, needed per day: 17
+  Tue 2021-03-02:    20 of 17    passed
+  Wed 2021-03-03:     0 of 17    failed
+  Thu 2021-03-04:    30 of 17    passed
+  Fri 2021-03-05:    10 of 17    open
+",
+            format_details(&status, &config)
+        );
+
+        let status = super::status(&challenge, &day(9), &storage, &config).unwrap();
+        assert_eq!(ChallengeState::Failed, status.state);
+        assert_eq!(0, status.days_left);
+        assert_eq!(6, status.days.len());
+
+        let upcoming = super::status(&challenge, &day(1), &storage, &config).unwrap();
+        assert_eq!(ChallengeState::Upcoming, upcoming.state);
+        assert!(upcoming.days.is_empty());
+        assert_eq!((6, 17), (upcoming.days_left, upcoming.required_daily));
+
+        let easy = pushups_challenge(ChallengeTarget::Total(60));
+        let status = super::status(&easy, &day(5), &storage, &config).unwrap();
+        assert_eq!(ChallengeState::Passed, status.state);
+    }
+
+    #[test]
+    fn daily_target() {
+        let storage = storage();
+        let config = Config::new("/nonexistent");
+        let mut challenge = pushups_challenge(ChallengeTarget::Daily(20));
+
+        let status = status(&challenge, &day(2), &storage, &config).unwrap();
+        assert_eq!(ChallengeState::Running, status.state);
+        assert_eq!((6, 20), (status.days_left, status.required_daily));
+
+        let status = super::status(&challenge, &day(4), &storage, &config).unwrap();
+        assert_eq!(ChallengeState::Failed, status.state);
+        assert_eq!(33, status.percent());
+        assert_eq!(
+            "pushups: Pushups 20 per day from 2021-03-02 to 2021-03-07 - failed, 33%",
+            format_summary(&status, &config)
+        );
+
+        challenge.start = NaiveDate::from_ymd(2021, 3, 4);
+        challenge.end = challenge.start;
+        let status = super::status(&challenge, &day(4), &storage, &config).unwrap();
+        assert_eq!(ChallengeState::Passed, status.state);
+    }
+
+    #[test]
+    fn percent_of_huge_targets() {
+        let storage = storage();
+        let config = Config::new("/nonexistent");
+        let challenge = pushups_challenge(ChallengeTarget::Total(u32::MAX));
+
+        let mut status = status(&challenge, &day(5), &storage, &config).unwrap();
+        assert_eq!(0, status.percent());
+        status.reps = u32::MAX / 2;
+        assert_eq!(49, status.percent());
+        status.reps = u32::MAX;
+        assert_eq!(100, status.percent());
+    }
+
+    fn storage() -> MemoryStorage {
+        let storage = MemoryStorage::new(vec![
+            Category::new("Pushups", 1.0, vec!["pu"]),
+            Category::new("Burpees", 1.5, vec!["bu"]),
+        ]);
+        for (day_of_month, reps, category) in &[
+            (1, 50, "Pushups"),
+            (2, 20, "Pushups"),
+            (3, 40, "Burpees"),
+            (4, 30, "Pushups"),
+            (5, 10, "Pushups"),
+            (8, 50, "Pushups"),
+        ] {
+            storage
+                .store(&Activity {
+                    timestamp: day(*day_of_month).and_hms(12, 0, 0),
+                    reps: *reps,
+                    category: category.to_string(),
+                })
+                .unwrap();
+        }
+        storage
+    }
+
+    fn pushups_challenge(target: ChallengeTarget) -> Challenge {
+        Challenge {
+            name: "pushups".to_string(),
+            category: "Pushups".to_string(),
+            start: NaiveDate::from_ymd(2021, 3, 2),
+            end: NaiveDate::from_ymd(2021, 3, 7),
+            target,
+        }
+    }
+
+    fn day(day_of_month: u32) -> Date<Local> {
+        Local.ymd(2021, 3, day_of_month)
+    }
+
+    fn days(status: &ChallengeStatus) -> Vec<(u32, bool)> {
+        status.days.iter().map(|d| (d.reps, d.passed())).collect()
+    }
+}
@@ -0,0 +1,286 @@
+use crate::error::Error;
+use crate::model::{Activity, Config, StorageBackend};
+use crate::storage::{self, Storage};
+use chrono::{DateTime, Local};
+use std::collections::HashSet;
+use std::fmt;
+
+//
+// Findings ---------------------------------
+//
+
+/// A problem found in the stored data
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// A data file can't be read at all
+    UnreadableFile { file: String, msg: String },
+    /// A line of a data file can't be parsed
+    MalformedLine {
+        file: String,
+        line: usize,
+        content: String,
+    },
+    /// A category is defined more than once. Only the first definition is used.
+    DuplicateCategory {
+        file: String,
+        line: usize,
+        name: String,
+    },
+    /// An activity is stored in the file of another month, so reports don't find it
+    WrongFile {
+        file: String,
+        line: usize,
+        activity: Activity,
+    },
+    /// An activity refers to a category that is not known by its name. `alias_of` is the
+    /// category if the name is an alias.
+    UnknownCategory {
+        activity: Activity,
+        alias_of: Option<String>,
+    },
+    /// An activity is stored more than once
+    Duplicate { activity: Activity },
+    /// An activity has a timestamp in the future
+    FutureTimestamp { activity: Activity },
+}
+
+/// A problem, plus whether it was repaired
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub problem: Problem,
+    pub fixed: bool,
+}
+
+impl Finding {
+    pub fn new(problem: Problem, fixed: bool) -> Finding {
+        Finding { problem, fixed }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::UnreadableFile { file, msg } => write!(f, "{}: {}", file, msg),
+            Problem::MalformedLine {
+                file,
+                line,
+                content,
+            } => write!(f, "{}, line {}: Unreadable entry <{}>", file, line, content),
+            Problem::DuplicateCategory { file, line, name } => write!(
+                f,
+                "{}, line {}: Category '{}' is already defined above",
+                file, line, name
+            ),
+            Problem::WrongFile {
+                file,
+                line,
+                activity,
+            } => write!(
+                f,
+                "{}, line {}: Entry from {} is in the file of another month",
+                file,
+                line,
+                activity.timestamp.format("%Y-%m-%d")
+            ),
+            Problem::UnknownCategory {
+                activity,
+                alias_of: Some(name),
+            } => write!(
+                f,
+                "{}: Category '{}' is an alias of '{}'",
+                describe(activity),
+                &activity.category,
+                name
+            ),
+            Problem::UnknownCategory {
+                activity,
+                alias_of: None,
+            } => write!(
+                f,
+                "{}: Category '{}' is not known",
+                describe(activity),
+                &activity.category
+            ),
+            Problem::Duplicate { activity } => write!(f, "{}: Duplicate entry", describe(activity)),
+            Problem::FutureTimestamp { activity } => {
+                write!(f, "{}: Timestamp is in the future", describe(activity))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            self.problem,
+            if self.fixed { " [fixed]" } else { "" }
+        )
+    }
+}
+
+//
+// Checks -----------------------------------
+//
+
+/// Check all stored data for problems. With `fix`, repair everything that can be repaired
+/// without guessing: Unreadable lines are commented out, entries in the wrong file are moved,
+/// duplicates are removed and aliases are replaced by the category name.
+pub fn run(storage: &dyn Storage, config: &Config, fix: bool) -> Result<Vec<Finding>, Error> {
+    let mut findings = match config.storage_backend {
+        StorageBackend::Text => storage::check_files(config, fix)?,
+        StorageBackend::Sqlite => Vec::new(),
+    };
+
+    let categories = storage.read_categories()?;
+    let now = Local::now();
+    let mut seen = HashSet::new();
+
+    for activity in storage.read_all()? {
+        if !seen.insert(key(&activity)) {
+            if fix {
+                storage.delete(&activity)?;
+            }
+            findings.push(Finding::new(Problem::Duplicate { activity }, fix));
+            continue;
+        }
+
+        if categories.get(&activity.category).is_none() {
+            let alias_of = categories
+                .find(&activity.category)
+                .map(|c| c.name.to_string());
+            let fixed = match (&alias_of, fix) {
+                (Some(name), true) => {
+                    let mut renamed = activity.clone();
+                    renamed.category = name.to_string();
+                    storage.update(&activity, &renamed)?;
+                    true
+                }
+                _ => false,
+            };
+
+            findings.push(Finding::new(
+                Problem::UnknownCategory {
+                    activity: activity.clone(),
+                    alias_of,
+                },
+                fixed,
+            ));
+        }
+
+        if activity.timestamp > now {
+            findings.push(Finding::new(
+                Problem::FutureTimestamp {
+                    activity: activity.clone(),
+                },
+                false,
+            ));
+        }
+    }
+
+    Ok(findings)
+}
+
+//
+// Helpers ----------------------------------
+//
+
+/// The fields identifying an activity, for duplicate detection
+fn key(activity: &Activity) -> (DateTime<Local>, String, u32) {
+    (
+        activity.timestamp,
+        activity.category.to_string(),
+        activity.reps,
+    )
+}
+
+fn describe(activity: &Activity) -> String {
+    format!(
+        "{} {} {}",
+        activity.timestamp.format("%Y-%m-%d %H:%M:%S"),
+        activity.reps,
+        &activity.category
+    )
+}
+
+//
+// Tests ---------------------------------
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Category;
+    use crate::storage::memory::MemoryStorage;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn generic_checks() {
+        let storage = MemoryStorage::new(vec![Category::new("Pushups", 1.0, vec!["pu"])]);
+        let config = Config {
+            storage_backend: StorageBackend::Sqlite, // no files to scan
+            ..Config::new("/nonexistent")
+        };
+
+        let ok = activity(1, "Pushups");
+        let alias = activity(2, "pu");
+        let unknown = activity(3, "Lunges");
+        let mut future = Activity::new(5, "Pushups");
+        future.timestamp = Local::now() + Duration::days(2);
+        for activity in &[&ok, &ok, &alias, &unknown, &future] {
+            storage.store(activity).unwrap();
+        }
+
+        let findings = run(&storage, &config, false).unwrap();
+        assert_eq!(
+            vec![
+                Finding::new(
+                    Problem::Duplicate {
+                        activity: ok.clone()
+                    },
+                    false
+                ),
+                Finding::new(
+                    Problem::UnknownCategory {
+                        activity: alias.clone(),
+                        alias_of: Some("Pushups".to_string())
+                    },
+                    false
+                ),
+                Finding::new(
+                    Problem::UnknownCategory {
+                        activity: unknown.clone(),
+                        alias_of: None
+                    },
+                    false
+                ),
+                Finding::new(
+                    Problem::FutureTimestamp {
+                        activity: future.clone()
+                    },
+                    false
+                ),
+            ],
+            findings
+        );
+
+        let fixed: Vec<bool> = run(&storage, &config, true)
+            .unwrap()
+            .iter()
+            .map(|f| f.fixed)
+            .collect();
+        assert_eq!(vec![true, true, false, false], fixed);
+
+        // only the unfixable problems remain
+        assert_eq!(2, run(&storage, &config, false).unwrap().len());
+        assert_eq!(4, storage.read_all().unwrap().len());
+    }
+
+    fn activity(day: u32, category: &str) -> Activity {
+        Activity {
+            timestamp: Local.ymd(2021, 2, day).and_hms(10, 0, 0),
+            reps: 10,
+            category: category.to_string(),
+        }
+    }
+}
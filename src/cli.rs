@@ -1,23 +1,43 @@
-use crate::error::ParseError;
-use crate::model::Config;
+use crate::error::Error;
+use crate::model::{ChallengeTarget, Config, StorageBackend};
 use anyhow::{bail, Result};
-use clap::{arg_enum, crate_authors, crate_version, App, Arg, ArgGroup, ArgMatches};
+use chrono::NaiveDate;
+use clap::{
+    arg_enum, crate_authors, crate_version, App, AppSettings, Arg, ArgGroup, ArgMatches, ErrorKind,
+};
 use directories::BaseDirs;
 use lazy_static::lazy_static;
 use regex::Regex;
 use std::ffi::OsString;
 
+mod completions;
+
 lazy_static! {
     static ref ACTIVITY_PATTERN: Regex = Regex::new(r"^(\d+)([a-zA-Z_]\w*)$").unwrap();
-    static ref REPORT_PATTERN: Regex = Regex::new(r"^[rR]([dmwDMW])$").unwrap();
+    /// 'r', the kind ('d', 'w', 'm', 'y' or a number of days), optionally a category (except for
+    /// 'd'), e.g. 'rwpu', 'rm-bu' or 'r30'
+    static ref REPORT_PATTERN: Regex =
+        Regex::new(r"^[rR](?:([dwmyDWMY])|(\d+))(?:-?([a-zA-Z_]\w*))?$").unwrap();
+}
+
+#[derive(PartialEq, Debug)]
+pub enum ReportKind {
+    Day,
+    Week,
+    Month,
+    Year,
+    /// The given number of days up to today
+    Days(u32),
+    /// Adherence to the training plan
+    Plan,
 }
 
 arg_enum! {
     #[derive(PartialEq, Debug)]
-    pub enum ReportKind {
-        Day,
-        Week,
-        Month
+    pub enum Shell {
+        Bash,
+        Zsh,
+        Fish
     }
 }
 
@@ -26,6 +46,10 @@ pub enum CliAction {
     AddActivity {
         repetitions: u32,
         category: String,
+        /// Create the category if it is not known yet
+        create: bool,
+        /// Alias for the category when creating it
+        alias: Option<String>,
     },
     Report {
         kind: ReportKind,
@@ -33,6 +57,79 @@ pub enum CliAction {
         sliding: bool,
     },
     System,
+    Migrate {
+        to: StorageBackend,
+    },
+    MergeConflicts,
+    Upgrade,
+    Check {
+        /// Repair the problems that can be repaired automatically
+        fix: bool,
+    },
+    Backup {
+        action: BackupAction,
+    },
+    /// Compare today's progress against the daily goals
+    Remind,
+    Challenge {
+        action: ChallengeAction,
+    },
+    Plan {
+        action: PlanAction,
+    },
+    /// Leaderboard of several people's data directories
+    TeamReport {
+        /// Names of the members and their data directories
+        members: Vec<(String, String)>,
+        /// Category file whose weights apply to all members, instead of their own
+        categories: Option<String>,
+        days: u32,
+    },
+    /// Print the completion script for the given shell
+    Completions {
+        shell: Shell,
+    },
+    /// Print all active category names and aliases, for dynamic shell completion
+    CompleteCategories,
+    /// Print the given text (help or version) and exit successfully
+    Info {
+        text: String,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BackupAction {
+    List,
+    Create,
+    Restore { id: String },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ChallengeAction {
+    List,
+    Add {
+        name: String,
+        category: String,
+        target: ChallengeTarget,
+        /// First day, default today
+        start: Option<NaiveDate>,
+        /// Last day, alternatively given as number of days
+        end: Option<NaiveDate>,
+        days: Option<u32>,
+    },
+    /// Progress of the given challenge, or of all running ones
+    Status {
+        name: Option<String>,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PlanAction {
+    /// Replace the training plan with the one in the given file
+    Load {
+        file: String,
+    },
+    Clear,
 }
 
 #[derive(Debug)]
@@ -42,27 +139,25 @@ pub struct RunContext {
 }
 
 impl RunContext {
-    pub fn new<T>(args: T) -> Result<RunContext, String>
+    pub fn new<T>(args: T) -> Result<RunContext, Error>
     where
         T: Iterator<Item = String>,
     {
         let mut ctx = RunContext {
-            config: Config {
-                data_dir: default_data_dir(),
-            },
+            config: Config::new(default_data_dir()),
             action: CliAction::System,
         };
 
-        match parse_cmdline(args) {
-            Ok(action) => {
-                ctx.action = action;
-                Ok(ctx)
-            }
-            Err(msg) => Err(format!("{:?}", msg)),
-        }
+        ctx.action = parse_cmdline(args)?;
+        Ok(ctx)
     }
 }
 
+/// The completion script for the given shell
+pub fn completion_script(shell: &Shell) -> String {
+    completions::script(shell, setup_clap_app())
+}
+
 //
 // functions -------------------------------------
 //
@@ -71,7 +166,7 @@ fn parse_activity(spec: &str) -> Result<CliAction> {
     let groups = match ACTIVITY_PATTERN.captures(spec) {
         Some(groups) => groups,
         None => {
-            bail!(ParseError::new("Unable to parse activity"));
+            bail!("Unable to parse activity");
         }
     };
 
@@ -81,38 +176,139 @@ fn parse_activity(spec: &str) -> Result<CliAction> {
     Ok(CliAction::AddActivity {
         repetitions,
         category,
+        create: false,
+        alias: None,
     })
 }
 
-fn setup_clap_app() -> App<'static, 'static> {
+pub(crate) fn setup_clap_app() -> App<'static, 'static> {
     App::new("naday")
         .version(crate_version!())
         .author(crate_authors!())
         .about("A little tool for tracking (physical) excercise of the 'N repetitions a day' variant - 100 pushups per day, 10'000 steps per day etc.")
+        .setting(AppSettings::ColorNever)
+        .after_help(
+"EXIT CODES:
+    0    Success
+    2    Invalid command line
+    3    Unknown, ambiguous or archived category
+    4    Storage failure
+    5    Invalid data or config file
+    6    Conflict with existing data
+    7    Invalid input values
+    8    Behind the daily goals (remind)
+    9    Problems found in the stored data (check)")
         .arg(Arg::from_usage("[SHORTHAND] 'Shorthand notation for the most common use cases'")
             .long_help(
 "'18pu' is short for 'log 18pu'
 'rd' is short for 'report --day'
-'rw' is short for 'report --week")
-            .conflicts_with_all(&["log", "system", "report"]))
+'rw' is short for 'report --week'
+'rm', 'ry' and 'r30' are short for 'report --month', '--year' and '--days=30'
+'rwpu' and 'rw-pu' are short for 'report --week --category=pu'")
+            .conflicts_with_all(&["log", "system", "report", "migrate", "merge-conflicts", "upgrade", "check", "backup", "remind", "challenge", "plan", "team", "completions", "complete-categories"]))
         .subcommand(
             App::new("log").about("Log an activity")
-                .arg(Arg::from_usage("[SPEC] 'Shorthand notation of the activity to log'"))
+                .arg(Arg::from_usage("<SPEC> 'Shorthand notation of the activity to log'"))
+                .arg(Arg::from_usage("--create 'Create the category (with weight 1) if it is not known yet'"))
+                .arg(Arg::from_usage("--alias=[ALIAS] 'Alias to add when creating the category'").requires("create"))
         )
         .subcommand(
             App::new("system").about("Get information on the tool's environment and settings")
         )
         .subcommand(
             App::new("report").about("Generate a report on logged activities")
-                .arg(Arg::from_usage("-d, --day 'Print detailed report for today'"))
+                .arg(Arg::from_usage("-d, --day 'Print detailed report for today, with the targets of the training plan or the daily goals'"))
                 .arg(Arg::from_usage("-w, --week 'Print a report of the current week'"))
                 .arg(Arg::from_usage("-m, --month 'Print a report of the current month'"))
-                .group(ArgGroup::with_name("report_kind").args(&["day", "week", "month"]).required(false).multiple(false))
-                .arg(Arg::from_usage("-c, --category=<NAME_OR_ALIAS> 'print stats on that category instead of the total'").required(false))
+                .arg(Arg::from_usage("-y, --year 'Print a report of the past year'"))
+                .arg(Arg::from_usage("--days=[N] 'Print a report of the past N days'").validator(validate_days))
+                .arg(Arg::from_usage("--plan 'Print the adherence to the training plan so far'"))
+                .group(ArgGroup::with_name("report_kind").args(&["day", "week", "month", "year", "days", "plan"]).required(false).multiple(false))
+                .arg(Arg::from_usage("-c, --category=<NAME_OR_ALIAS> 'print stats on that category instead of the total'").required(false).conflicts_with_all(&["day", "plan"]))
+        )
+        .subcommand(
+            App::new("migrate").about("Move all data to another storage backend and switch to it")
+                .arg(Arg::from_usage("--to=<BACKEND> 'The storage backend to migrate to'").possible_values(&["text", "sqlite"]))
+        )
+        .subcommand(
+            App::new("check").about("Check the stored data for problems")
+                .arg(Arg::from_usage("--fix 'Repair all problems that can be repaired automatically'"))
+        )
+        .subcommand(
+            App::new("backup").about("Manage backups of the data directory")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(App::new("list").about("List all backups"))
+                .subcommand(App::new("create").about("Back up the data directory now"))
+                .subcommand(
+                    App::new("restore").about("Replace all data with a backup (the current state is backed up before)")
+                        .arg(Arg::from_usage("<ID> 'The backup to restore, as shown by 'backup list''"))
+                )
+        )
+        .subcommand(
+            App::new("remind").about("Check today's progress against the daily goals of all categories and nudge if behind (e.g. from cron)")
+        )
+        .subcommand(
+            App::new("challenge").about("Manage time-boxed targets like '3000 pushups in October'")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(App::new("list").about("List all challenges with their progress"))
+                .subcommand(
+                    App::new("add").about("Add a challenge")
+                        .arg(Arg::from_usage("<NAME> 'Name of the challenge, e.g. october_pushups'"))
+                        .arg(Arg::from_usage("-c, --category=<NAME_OR_ALIAS> 'The category of the challenge'"))
+                        .arg(Arg::from_usage("--total=[REPS] 'Repetitions to reach over the whole challenge'").validator(validate_reps))
+                        .arg(Arg::from_usage("--daily=[REPS] 'Repetitions to reach on each day'").validator(validate_reps))
+                        .group(ArgGroup::with_name("target").args(&["total", "daily"]).required(true))
+                        .arg(Arg::from_usage("--start=[DATE] 'First day (YYYY-MM-DD), default today'").validator(validate_date))
+                        .arg(Arg::from_usage("--end=[DATE] 'Last day (YYYY-MM-DD)'").validator(validate_date))
+                        .arg(Arg::from_usage("--days=[N] 'Number of days'").validator(validate_days))
+                        .group(ArgGroup::with_name("duration").args(&["end", "days"]).required(true))
+                )
+                .subcommand(
+                    App::new("status").about("Show the progress of a challenge day by day (default: all running ones)")
+                        .arg(Arg::from_usage("[NAME] 'The challenge to show'"))
+                )
+        )
+        .subcommand(
+            App::new("plan").about("Manage the training plan: daily targets per category that change over time")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    App::new("load").about("Replace the training plan with the one in a file")
+                        .arg(Arg::from_usage("<FILE> 'The plan file, see the README for its format'"))
+                )
+                .subcommand(App::new("clear").about("Remove the training plan, so the daily goals apply again"))
+        )
+        .subcommand(
+            App::new("team").about("Compare the data directories of several people")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    App::new("report").about("Print a leaderboard of weighted totals, streaks and goal completion")
+                        .arg(Arg::from_usage("--dirs=<MEMBERS> 'Names and data directories of the members, e.g. alice=/sync/alice,bob=/sync/bob'").validator(validate_members))
+                        .arg(Arg::from_usage("--categories=[FILE] 'Category file whose weights apply to all members, instead of their own'"))
+                        .arg(Arg::from_usage("-w, --week 'Report on the past 7 days (default)'"))
+                        .arg(Arg::from_usage("-m, --month 'Report on the past 31 days'"))
+                        .arg(Arg::from_usage("-y, --year 'Report on the past 365 days'"))
+                        .arg(Arg::from_usage("--days=[N] 'Report on the past N days'").validator(validate_days))
+                        .group(ArgGroup::with_name("period").args(&["week", "month", "year", "days"]).required(false).multiple(false))
+                )
+        )
+        .subcommand(
+            App::new("completions").about("Print the shell completion script (e.g. 'source <(naday completions bash)')")
+                .arg(Arg::from_usage("<SHELL> 'The shell to generate the script for'").possible_values(&Shell::variants()).case_insensitive(true))
+        )
+        .subcommand(
+            App::new("complete-categories").about("Print all category names and aliases (used by the completion scripts)")
+                .setting(AppSettings::Hidden)
+        )
+        .subcommand(
+            App::new("merge-conflicts").about("Merge sync conflict copies (e.g. from Syncthing) of the data files")
+        )
+        .subcommand(
+            App::new("upgrade").about("Rewrite data files in older format versions in the current format")
         )
 }
 
-fn parse_cmdline<I, T>(args: I) -> Result<CliAction, ()>
+#[allow(clippy::needless_borrow)]
+fn parse_cmdline<I, T>(args: I) -> Result<CliAction, Error>
 where
     I: IntoIterator<Item = T>,
     T: Into<OsString> + Clone,
@@ -122,8 +318,13 @@ where
     let matches = match app.get_matches_from_safe(args) {
         Ok(m) => m,
         Err(error) => {
-            eprintln!("{}", error);
-            return Err(());
+            return match error.kind {
+                // clap prints the version itself (without newline), the help text is left to us
+                ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed => Ok(CliAction::Info {
+                    text: error.message,
+                }),
+                _ => Err(Error::usage(error.message.trim_start_matches("error: "))),
+            };
         }
     };
 
@@ -131,13 +332,71 @@ where
         return Ok(eval_report(report));
     } else if let Some(_system) = matches.subcommand_matches("system") {
         return Ok(CliAction::System);
+    } else if let Some(migrate) = matches.subcommand_matches("migrate") {
+        // value is required and restricted to valid backends
+        let to = migrate.value_of("to").unwrap().parse().unwrap();
+        return Ok(CliAction::Migrate { to });
+    } else if let Some(check) = matches.subcommand_matches("check") {
+        return Ok(CliAction::Check {
+            fix: check.is_present("fix"),
+        });
+    } else if let Some(backup) = matches.subcommand_matches("backup") {
+        let action = match backup.subcommand() {
+            ("create", _) => BackupAction::Create,
+            ("restore", Some(restore)) => BackupAction::Restore {
+                id: restore.value_of("ID").unwrap().to_string(), // required parameter
+            },
+            _ => BackupAction::List,
+        };
+        return Ok(CliAction::Backup { action });
+    } else if let Some(_remind) = matches.subcommand_matches("remind") {
+        return Ok(CliAction::Remind);
+    } else if let Some(challenge) = matches.subcommand_matches("challenge") {
+        return Ok(CliAction::Challenge {
+            action: eval_challenge(challenge),
+        });
+    } else if let Some(plan) = matches.subcommand_matches("plan") {
+        let action = match plan.subcommand() {
+            ("load", Some(load)) => PlanAction::Load {
+                file: load.value_of("FILE").unwrap().to_string(), // required parameter
+            },
+            _ => PlanAction::Clear,
+        };
+        return Ok(CliAction::Plan { action });
+    } else if let Some(team) = matches.subcommand_matches("team") {
+        // 'report' is the only subcommand, and required
+        let report = team.subcommand_matches("report").unwrap();
+        return Ok(eval_team_report(report));
+    } else if let Some(completions) = matches.subcommand_matches("completions") {
+        // value is required and restricted to valid shells
+        let shell = completions.value_of("SHELL").unwrap().parse().unwrap();
+        return Ok(CliAction::Completions { shell });
+    } else if let Some(_categories) = matches.subcommand_matches("complete-categories") {
+        return Ok(CliAction::CompleteCategories);
+    } else if let Some(_merge) = matches.subcommand_matches("merge-conflicts") {
+        return Ok(CliAction::MergeConflicts);
+    } else if let Some(_upgrade) = matches.subcommand_matches("upgrade") {
+        return Ok(CliAction::Upgrade);
     } else if let Some(log) = matches.subcommand_matches("log") {
         let spec = log.value_of("SPEC").unwrap(); // required parameter
-        if let Ok(activity) = parse_activity(&spec) {
-            return Ok(activity);
+        if let Ok(CliAction::AddActivity {
+            repetitions,
+            category,
+            ..
+        }) = parse_activity(spec)
+        {
+            return Ok(CliAction::AddActivity {
+                repetitions,
+                category,
+                create: log.is_present("create"),
+                alias: log.value_of("alias").map(|alias| alias.to_string()),
+            });
         } else {
-            eprintln!("{}", log.usage());
-            return Err(());
+            return Err(Error::usage(format!(
+                "Could not parse activity spec '{}'\n\n{}",
+                spec,
+                log.usage()
+            )));
         }
     } else if let Some(shorthand) = matches.value_of("SHORTHAND") {
         return parse_shorthand(&shorthand);
@@ -146,11 +405,18 @@ where
     Ok(CliAction::System)
 }
 
+#[allow(clippy::manual_map)]
 fn eval_report(report: &ArgMatches) -> CliAction {
     let kind = if report.is_present("day") {
         ReportKind::Day
     } else if report.is_present("week") {
         ReportKind::Week
+    } else if report.is_present("year") {
+        ReportKind::Year
+    } else if let Some(days) = report.value_of("days") {
+        ReportKind::Days(days.parse().unwrap()) // checked by the validator
+    } else if report.is_present("plan") {
+        ReportKind::Plan
     } else {
         ReportKind::Month
     };
@@ -167,14 +433,88 @@ fn eval_report(report: &ArgMatches) -> CliAction {
     }
 }
 
-fn parse_shorthand(spec: &str) -> Result<CliAction, ()> {
+fn eval_challenge(challenge: &ArgMatches) -> ChallengeAction {
+    match challenge.subcommand() {
+        ("add", Some(add)) => {
+            // all values are checked by the validators, and the groups ensure a target is given
+            let target = match add.value_of("total") {
+                Some(total) => ChallengeTarget::Total(total.parse().unwrap()),
+                None => ChallengeTarget::Daily(add.value_of("daily").unwrap().parse().unwrap()),
+            };
+            let date = |name| add.value_of(name).map(|date| parse_date(date).unwrap());
+
+            ChallengeAction::Add {
+                name: add.value_of("NAME").unwrap().to_string(),
+                category: add.value_of("category").unwrap().to_string(),
+                target,
+                start: date("start"),
+                end: date("end"),
+                days: add.value_of("days").map(|days| days.parse().unwrap()),
+            }
+        }
+        ("status", Some(status)) => ChallengeAction::Status {
+            name: status.value_of("NAME").map(|name| name.to_string()),
+        },
+        _ => ChallengeAction::List,
+    }
+}
+
+fn eval_team_report(report: &ArgMatches) -> CliAction {
+    let days = if report.is_present("month") {
+        31
+    } else if report.is_present("year") {
+        365
+    } else if let Some(days) = report.value_of("days") {
+        days.parse().unwrap() // checked by the validator
+    } else {
+        7
+    };
+
+    CliAction::TeamReport {
+        members: parse_members(report.value_of("dirs").unwrap()).unwrap(), // checked by the validator
+        categories: report.value_of("categories").map(|file| file.to_string()),
+        days,
+    }
+}
+
+/// Parse a list of team members like 'alice=/path/a,bob=/path/b'
+fn parse_members(spec: &str) -> Result<Vec<(String, String)>, String> {
+    let mut members: Vec<(String, String)> = Vec::new();
+    for member in spec.split(',') {
+        match member.split_once('=') {
+            Some((name, dir)) if !name.trim().is_empty() && !dir.trim().is_empty() => {
+                let name = name.trim();
+                if members.iter().any(|(known, _)| known == name) {
+                    return Err(format!("Member '{}' is given more than once", name));
+                }
+                members.push((name.to_string(), dir.trim().to_string()));
+            }
+            _ => {
+                return Err(format!(
+                    "Invalid member '{}' - expected 'name=/path/to/data_dir'",
+                    member
+                ))
+            }
+        }
+    }
+
+    Ok(members)
+}
+
+fn validate_members(spec: String) -> Result<(), String> {
+    parse_members(&spec).map(|_| ())
+}
+
+fn parse_shorthand(spec: &str) -> Result<CliAction, Error> {
     if let Ok(activity) = parse_activity(spec) {
         Ok(activity)
     } else if let Ok(report) = parse_report(spec) {
         Ok(report)
     } else {
-        eprintln!("Could not parse shorthand spec '{}'", spec);
-        Err(())
+        Err(Error::usage(format!(
+            "Could not parse shorthand spec '{}'",
+            spec
+        )))
     }
 }
 
@@ -182,23 +522,62 @@ fn parse_report(spec: &str) -> Result<CliAction> {
     let groups = match REPORT_PATTERN.captures(spec) {
         Some(groups) => groups,
         None => {
-            bail!(ParseError::new("Unable to parse report shorthand",))
+            bail!("Unable to parse report shorthand")
         }
     };
 
-    let kind = match &groups[1] {
-        "d" | "D" => ReportKind::Day,
-        "w" | "W" => ReportKind::Week,
-        _ => ReportKind::Month,
+    let category = groups.get(3).map(|cat| cat.as_str().to_string());
+    let kind = match groups.get(1).map(|kind| kind.as_str()) {
+        Some("d") | Some("D") if category.is_some() => {
+            bail!("The day report covers all categories")
+        }
+        Some("d") | Some("D") => ReportKind::Day,
+        Some("w") | Some("W") => ReportKind::Week,
+        Some("y") | Some("Y") => ReportKind::Year,
+        Some(_) => ReportKind::Month,
+        None => match validate_days(groups[2].to_string()) {
+            Ok(_) => ReportKind::Days(groups[2].parse()?),
+            Err(msg) => bail!(msg),
+        },
     };
 
     Ok(CliAction::Report {
         kind,
-        category: None,
+        category,
         sliding: true,
     })
 }
 
+/// Check a number of days for a report
+fn validate_days(days: String) -> Result<(), String> {
+    match days.parse::<u32>() {
+        Ok(days) if (1..=3660).contains(&days) => Ok(()),
+        _ => Err(format!(
+            "Invalid number of days '{}' - expected 1 to 3660",
+            days
+        )),
+    }
+}
+
+fn validate_reps(reps: String) -> Result<(), String> {
+    match reps.parse::<u32>() {
+        Ok(reps) if reps > 0 => Ok(()),
+        _ => Err(format!(
+            "Invalid number of repetitions '{}' - expected a number greater than 0",
+            reps
+        )),
+    }
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date '{}' - expected e.g. 2026-10-31", date))
+}
+
+fn validate_date(date: String) -> Result<(), String> {
+    parse_date(&date).map(|_| ())
+}
+
 fn default_data_dir() -> String {
     let homedir = BaseDirs::new().unwrap();
     let homedir = homedir.home_dir();
@@ -218,6 +597,90 @@ mod tests {
         let ctx = RunContext::new(build_args(vec!["system"]).into_iter());
         assert_eq!(CliAction::System, ctx.unwrap().action);
 
+        let ctx = RunContext::new(build_args(vec!["merge-conflicts"]).into_iter());
+        assert_eq!(CliAction::MergeConflicts, ctx.unwrap().action);
+
+        let ctx = RunContext::new(build_args(vec!["upgrade"]).into_iter());
+        assert_eq!(CliAction::Upgrade, ctx.unwrap().action);
+
+        let ctx = RunContext::new(build_args(vec!["check", "--fix"]).into_iter());
+        assert_eq!(CliAction::Check { fix: true }, ctx.unwrap().action);
+
+        let ctx = RunContext::new(build_args(vec!["remind"]).into_iter());
+        assert_eq!(CliAction::Remind, ctx.unwrap().action);
+
+        let ctx = RunContext::new(build_args(vec!["challenge", "status"]).into_iter());
+        assert_eq!(
+            CliAction::Challenge {
+                action: ChallengeAction::Status { name: None }
+            },
+            ctx.unwrap().action
+        );
+
+        let ctx = RunContext::new(
+            build_args(vec!["team", "report", "--dirs=alice=/a, bob = /b", "-m"]).into_iter(),
+        );
+        assert_eq!(
+            CliAction::TeamReport {
+                members: vec![
+                    ("alice".to_string(), "/a".to_string()),
+                    ("bob".to_string(), "/b".to_string())
+                ],
+                categories: None,
+                days: 31
+            },
+            ctx.unwrap().action
+        );
+        let ctx = RunContext::new(
+            build_args(vec![
+                "team",
+                "report",
+                "--dirs",
+                "a=/a",
+                "--categories=c.txt",
+            ])
+            .into_iter(),
+        );
+        assert!(matches!(
+            ctx.unwrap().action,
+            CliAction::TeamReport {
+                days: 7,
+                categories: Some(_),
+                ..
+            }
+        ));
+        for dirs in &["alice", "alice=/a,alice=/b", "=/a", "a=/a,"] {
+            let ctx =
+                RunContext::new(build_args(vec!["team", "report", "--dirs", dirs]).into_iter());
+            assert_eq!(2, ctx.unwrap_err().exit_code());
+        }
+
+        let ctx = RunContext::new(build_args(vec!["completions", "zsh"]).into_iter());
+        assert_eq!(
+            CliAction::Completions { shell: Shell::Zsh },
+            ctx.unwrap().action
+        );
+
+        let ctx = RunContext::new(build_args(vec!["backup", "list"]).into_iter());
+        assert_eq!(
+            CliAction::Backup {
+                action: BackupAction::List
+            },
+            ctx.unwrap().action
+        );
+
+        let ctx = RunContext::new(
+            build_args(vec!["backup", "restore", "20261018-093000-manual"]).into_iter(),
+        );
+        assert_eq!(
+            CliAction::Backup {
+                action: BackupAction::Restore {
+                    id: "20261018-093000-manual".to_string()
+                }
+            },
+            ctx.unwrap().action
+        );
+
         let ctx = RunContext::new(build_args(vec!["report"]).into_iter());
         assert_eq!(
             CliAction::Report {
@@ -228,6 +691,16 @@ mod tests {
             ctx.unwrap().action
         );
 
+        let ctx = RunContext::new(build_args(vec!["report", "--days=30"]).into_iter());
+        assert_eq!(
+            CliAction::Report {
+                kind: ReportKind::Days(30),
+                category: None,
+                sliding: true,
+            },
+            ctx.unwrap().action
+        );
+
         let ctx =
             RunContext::new(build_args(vec!["report", "--week", "--category=pu"]).into_iter());
         assert_eq!(
@@ -240,6 +713,150 @@ mod tests {
         );
     }
 
+    #[test]
+    fn help_and_errors() {
+        let ctx = RunContext::new(build_args(vec!["--help"]).into_iter());
+        match ctx.unwrap().action {
+            CliAction::Info { text } => assert!(text.contains("EXIT CODES")),
+            action => panic!("Unexpected action {:?}", action),
+        }
+
+        let ctx = RunContext::new(build_args(vec!["report", "--fortnight"]).into_iter());
+        let error = ctx.unwrap_err();
+        assert_eq!(2, error.exit_code());
+        assert!(error
+            .to_string()
+            .starts_with("Found argument '--fortnight'"));
+    }
+
+    #[test]
+    fn migrate() {
+        let ctx = RunContext::new(build_args(vec!["migrate", "--to=sqlite"]).into_iter());
+        assert_eq!(
+            CliAction::Migrate {
+                to: StorageBackend::Sqlite
+            },
+            ctx.unwrap().action
+        );
+
+        let ctx = RunContext::new(build_args(vec!["migrate", "--to=csv"]).into_iter());
+        assert!(ctx.is_err());
+    }
+
+    #[test]
+    fn challenges() {
+        let args = vec![
+            "challenge",
+            "add",
+            "october",
+            "-c",
+            "pu",
+            "--total=3000",
+            "--start=2026-10-01",
+            "--end=2026-10-31",
+        ];
+        let ctx = RunContext::new(build_args(args).into_iter());
+        assert_eq!(
+            CliAction::Challenge {
+                action: ChallengeAction::Add {
+                    name: "october".to_string(),
+                    category: "pu".to_string(),
+                    target: ChallengeTarget::Total(3000),
+                    start: Some(NaiveDate::from_ymd(2026, 10, 1)),
+                    end: Some(NaiveDate::from_ymd(2026, 10, 31)),
+                    days: None,
+                }
+            },
+            ctx.unwrap().action
+        );
+
+        let args = vec![
+            "challenge",
+            "add",
+            "b30",
+            "-c=bu",
+            "--daily=100",
+            "--days=30",
+        ];
+        let ctx = RunContext::new(build_args(args).into_iter());
+        assert!(matches!(
+            ctx.unwrap().action,
+            CliAction::Challenge {
+                action: ChallengeAction::Add {
+                    target: ChallengeTarget::Daily(100),
+                    start: None,
+                    days: Some(30),
+                    ..
+                }
+            }
+        ));
+
+        for args in &[
+            vec!["challenge", "add", "x", "-c=bu", "--days=30"],
+            vec!["challenge", "add", "x", "-c=bu", "--daily=10"],
+            vec![
+                "challenge",
+                "add",
+                "x",
+                "-c=bu",
+                "--total=10",
+                "--daily=10",
+                "--days=3",
+            ],
+            vec!["challenge", "add", "x", "-c=bu", "--daily=0", "--days=3"],
+            vec![
+                "challenge",
+                "add",
+                "x",
+                "-c=bu",
+                "--daily=5",
+                "--end=31.10.2026",
+            ],
+        ] {
+            let ctx = RunContext::new(build_args(args.clone()).into_iter());
+            assert_eq!(2, ctx.unwrap_err().exit_code(), "{:?}", args);
+        }
+
+        let ctx = RunContext::new(build_args(vec!["plan", "load", "hundred.txt"]).into_iter());
+        assert_eq!(
+            CliAction::Plan {
+                action: PlanAction::Load {
+                    file: "hundred.txt".to_string()
+                }
+            },
+            ctx.unwrap().action
+        );
+        let ctx = RunContext::new(build_args(vec!["plan", "clear"]).into_iter());
+        assert_eq!(
+            CliAction::Plan {
+                action: PlanAction::Clear
+            },
+            ctx.unwrap().action
+        );
+        let ctx = RunContext::new(build_args(vec!["report", "--plan"]).into_iter());
+        assert!(matches!(
+            ctx.unwrap().action,
+            CliAction::Report {
+                kind: ReportKind::Plan,
+                ..
+            }
+        ));
+        for args in &[vec!["plan"], vec!["report", "--plan", "--day"]] {
+            let ctx = RunContext::new(build_args(args.clone()).into_iter());
+            assert!(ctx.is_err(), "{:?}", args);
+        }
+
+        let ctx = RunContext::new(build_args(vec!["challenge", "status", "b30"]).into_iter());
+        assert_eq!(
+            CliAction::Challenge {
+                action: ChallengeAction::Status {
+                    name: Some("b30".to_string())
+                }
+            },
+            ctx.unwrap().action
+        );
+    }
+
     #[test]
     fn activities() {
         let ctx = RunContext::new(build_args(vec!["16pu"]).into_iter());
@@ -247,6 +864,22 @@ mod tests {
 
         let ctx = RunContext::new(build_args(vec!["23h2"]).into_iter());
         assert_eq!(build_activity(23, "h2"), ctx.unwrap().action);
+
+        let ctx = RunContext::new(build_args(vec!["log", "12pu"]).into_iter());
+        assert_eq!(build_activity(12, "pu"), ctx.unwrap().action);
+
+        let ctx = RunContext::new(
+            build_args(vec!["log", "--create", "--alias=lu", "25Lunges"]).into_iter(),
+        );
+        assert_eq!(
+            CliAction::AddActivity {
+                repetitions: 25,
+                category: "Lunges".to_string(),
+                create: true,
+                alias: Some("lu".to_string()),
+            },
+            ctx.unwrap().action
+        );
     }
 
     #[test]
@@ -270,6 +903,35 @@ mod tests {
             },
             ctx.unwrap().action
         );
+
+        let reports = vec![
+            ("rwpu", ReportKind::Week, Some("pu")),
+            ("rm-bu", ReportKind::Month, Some("bu")),
+            ("ry", ReportKind::Year, None),
+            ("r30", ReportKind::Days(30), None),
+            ("R14-Pushups", ReportKind::Days(14), Some("Pushups")),
+        ];
+        for (spec, kind, category) in reports {
+            let ctx = RunContext::new(build_args(vec![spec]).into_iter());
+            assert_eq!(
+                CliAction::Report {
+                    kind,
+                    category: category.map(|c| c.to_string()),
+                    sliding: true,
+                },
+                ctx.unwrap().action,
+                "{}",
+                spec
+            );
+        }
+
+        for spec in &["r0", "rx", "rw-", "r99999999999", "rd-pu", "rdpu"] {
+            let ctx = RunContext::new(build_args(vec![spec]).into_iter());
+            assert_eq!(2, ctx.unwrap_err().exit_code(), "{}", spec);
+        }
+
+        let ctx = RunContext::new(build_args(vec!["report", "--day", "-c", "pu"]).into_iter());
+        assert_eq!(2, ctx.unwrap_err().exit_code());
     }
 
     fn build_args(raw: Vec<&str>) -> Vec<String> {
@@ -283,10 +945,13 @@ mod tests {
         args
     }
 
+    #[allow(clippy::redundant_field_names)]
     fn build_activity(repetitions: u32, category: &str) -> CliAction {
         CliAction::AddActivity {
             repetitions: repetitions,
             category: category.to_string(),
+            create: false,
+            alias: None,
         }
     }
 }
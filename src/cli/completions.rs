@@ -0,0 +1,116 @@
+use super::Shell;
+use clap::App;
+
+/// Generate the completion script for the given shell. On top of the static completions clap
+/// generates, category names and aliases are completed after a number ('18p<TAB>') and for
+/// `--category`, by calling `naday complete-categories`.
+pub fn script(shell: &Shell, mut app: App<'static, 'static>) -> String {
+    let clap_shell = match shell {
+        Shell::Bash => clap::Shell::Bash,
+        Shell::Zsh => clap::Shell::Zsh,
+        Shell::Fish => clap::Shell::Fish,
+    };
+
+    let mut buffer = Vec::new();
+    app.gen_completions_to("naday", clap_shell, &mut buffer);
+    let generated = String::from_utf8_lossy(&buffer).to_string();
+
+    match shell {
+        Shell::Bash => format!("{}\n{}", generated, BASH_DYNAMIC),
+        // the generated script ends with calling the static completion function
+        Shell::Zsh => format!(
+            "{}\n{}",
+            generated.trim_end().trim_end_matches("_naday \"$@\""),
+            ZSH_DYNAMIC
+        ),
+        Shell::Fish => format!("{}\n{}", generated, FISH_DYNAMIC),
+    }
+}
+
+//
+// Internals ---------------------------
+//
+
+const BASH_DYNAMIC: &str = r#"# dynamic completion of category names and aliases
+_naday_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    local prev="${COMP_WORDS[COMP_CWORD-1]}"
+    local categories
+
+    if [[ "$cur" =~ ^([0-9]+)(.*)$ ]]; then
+        categories="$(naday complete-categories 2>/dev/null)"
+        COMPREPLY=( $(compgen -P "${BASH_REMATCH[1]}" -W "$categories" -- "${BASH_REMATCH[2]}") )
+        return 0
+    fi
+    if [[ "$prev" == "-c" || "$prev" == "--category" || "$cur" == --category=* ]]; then
+        categories="$(naday complete-categories 2>/dev/null)"
+        COMPREPLY=( $(compgen -W "$categories" -- "${cur#--category=}") )
+        return 0
+    fi
+
+    _naday "$@"
+}
+
+complete -F _naday_dynamic -o bashdefault -o default naday
+"#;
+
+const ZSH_DYNAMIC: &str = r#"# dynamic completion of category names and aliases
+_naday_dynamic() {
+    setopt localoptions extendedglob
+    local -a categories
+    categories=(${(f)"$(naday complete-categories 2>/dev/null)"})
+
+    if [[ "$PREFIX" == [0-9]##* ]]; then
+        compset -P '[0-9]##'
+        compadd -a categories
+        return
+    fi
+    if [[ "${words[CURRENT-1]}" == (-c|--category) ]]; then
+        compadd -a categories
+        return
+    fi
+
+    _naday "$@"
+}
+
+_naday_dynamic "$@"
+"#;
+
+const FISH_DYNAMIC: &str = r#"# dynamic completion of category names and aliases
+function __naday_shorthand
+    set -l token (commandline -ct)
+    set -l reps (string match -r '^[0-9]+' -- $token)
+    and for category in (naday complete-categories 2>/dev/null)
+        echo $reps$category
+    end
+end
+
+complete -c naday -f -n 'string match -qr "^[0-9]+" -- (commandline -ct)' -a '(__naday_shorthand)'
+complete -c naday -n '__fish_seen_subcommand_from log' -f -a '(__naday_shorthand)'
+complete -c naday -n '__fish_seen_subcommand_from report' -s c -l category -x -a '(naday complete-categories 2>/dev/null)'
+"#;
+
+//
+// Tests ---------------------------------
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::setup_clap_app;
+
+    #[test]
+    fn scripts() {
+        let bash = script(&Shell::Bash, setup_clap_app());
+        assert!(bash.contains("_naday()"));
+        assert!(bash.contains("complete -F _naday_dynamic"));
+
+        let zsh = script(&Shell::Zsh, setup_clap_app());
+        assert!(zsh.starts_with("#compdef naday"));
+        assert!(zsh.trim_end().ends_with("_naday_dynamic \"$@\""));
+        assert_eq!(1, zsh.matches("_naday \"$@\"").count());
+
+        let fish = script(&Shell::Fish, setup_clap_app());
+        assert!(fish.contains("complete -c naday"));
+        assert!(fish.contains("naday complete-categories"));
+    }
+}
@@ -1,31 +1,244 @@
-use std::error::Error;
 use std::fmt;
+use std::io;
 
 //
 // Error types --------------------------
 //
 
+/// All errors reported by naday. Each kind of error maps to its own process exit code:
+///
+/// | Code | Meaning                                             |
+/// |------|-----------------------------------------------------|
+/// | 0    | Success                                             |
+/// | 2    | Invalid command line                                |
+/// | 3    | Unknown, ambiguous or archived category             |
+/// | 4    | Storage failure (I/O error, database error)         |
+/// | 5    | Invalid contents of a data or config file           |
+/// | 6    | The requested change conflicts with existing data   |
+/// | 7    | Invalid input values (e.g. zero repetitions)        |
+/// | 8    | Behind the daily goals (`naday remind`)             |
+/// | 9    | Problems found in the stored data (`naday check`)   |
 #[derive(Debug)]
-pub struct ParseError {
-    pub msg: String,
+pub enum Error {
+    /// Reading or writing a file failed
+    Io { msg: String, source: io::Error },
+    /// A data or config file has invalid contents
+    Parse {
+        file: String,
+        line: Option<usize>,
+        msg: String,
+    },
+    /// The given category name or alias is not known
+    UnknownCategory {
+        name: String,
+        suggestion: Option<String>,
+    },
+    /// The given prefix matches several categories
+    AmbiguousCategory {
+        name: String,
+        candidates: Vec<String>,
+    },
+    /// The given category is archived and can't be used for logging anymore
+    ArchivedCategory { name: String },
+    /// The requested change conflicts with existing data
+    Conflict { msg: String },
+    /// The command line could not be parsed
+    Usage { msg: String },
+    /// The command line is well-formed, but contains invalid values
+    Validation { msg: String },
+    /// Any other failure of the storage backend
+    Storage { msg: String },
+    /// The progress of the day is behind the daily goals
+    Behind { msg: String },
+    /// `naday check` found problems that have not been fixed
+    ProblemsRemaining { msg: String },
 }
 
-impl fmt::Display for ParseError {
+impl Error {
+    /// The process exit code for this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Usage { .. } => 2,
+            Error::UnknownCategory { .. }
+            | Error::AmbiguousCategory { .. }
+            | Error::ArchivedCategory { .. } => 3,
+            Error::Io { .. } | Error::Storage { .. } => 4,
+            Error::Parse { .. } => 5,
+            Error::Conflict { .. } => 6,
+            Error::Validation { .. } => 7,
+            Error::Behind { .. } => 8,
+            Error::ProblemsRemaining { .. } => 9,
+        }
+    }
+
+    pub fn parse<F: AsRef<str>, M: AsRef<str>>(file: F, line: Option<usize>, msg: M) -> Error {
+        Error::Parse {
+            file: file.as_ref().to_string(),
+            line,
+            msg: msg.as_ref().to_string(),
+        }
+    }
+
+    pub fn conflict<M: AsRef<str>>(msg: M) -> Error {
+        Error::Conflict {
+            msg: msg.as_ref().to_string(),
+        }
+    }
+
+    pub fn usage<M: AsRef<str>>(msg: M) -> Error {
+        Error::Usage {
+            msg: msg.as_ref().to_string(),
+        }
+    }
+
+    pub fn validation<M: AsRef<str>>(msg: M) -> Error {
+        Error::Validation {
+            msg: msg.as_ref().to_string(),
+        }
+    }
+
+    pub fn behind<M: AsRef<str>>(msg: M) -> Error {
+        Error::Behind {
+            msg: msg.as_ref().to_string(),
+        }
+    }
+
+    pub fn problems_remaining<M: AsRef<str>>(msg: M) -> Error {
+        Error::ProblemsRemaining {
+            msg: msg.as_ref().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "ParseError: {}", self.msg)
+        match self {
+            Error::Io { msg, .. } => write!(f, "{}", msg),
+            Error::Parse {
+                file,
+                line: Some(line),
+                msg,
+            } => write!(f, "{}, line {}: {}", file, line, msg),
+            Error::Parse {
+                file,
+                line: None,
+                msg,
+            } => write!(f, "{}: {}", file, msg),
+            Error::UnknownCategory {
+                name,
+                suggestion: Some(suggestion),
+            } => write!(
+                f,
+                "Activity category '{}' is not known. Did you mean '{}'?",
+                name, suggestion
+            ),
+            Error::UnknownCategory {
+                name,
+                suggestion: None,
+            } => write!(f, "Activity category '{}' is not known", name),
+            Error::AmbiguousCategory { name, candidates } => write!(
+                f,
+                "Activity category '{}' is ambiguous, it could be any of: {}",
+                name,
+                candidates.join(", ")
+            ),
+            Error::ArchivedCategory { name } => write!(
+                f,
+                "Activity category '{}' is archived and can't be used anymore",
+                name
+            ),
+            Error::Conflict { msg }
+            | Error::Usage { msg }
+            | Error::Validation { msg }
+            | Error::Storage { msg }
+            | Error::Behind { msg }
+            | Error::ProblemsRemaining { msg } => write!(f, "{}", msg),
+        }
     }
 }
 
-impl Error for ParseError {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
 
-impl ParseError {
-    pub fn new<T: AsRef<str>>(msg: T) -> ParseError {
-        ParseError {
-            msg: msg.as_ref().to_string(),
+/// Convert the errors of the storage internals. Typed errors and I/O errors are preserved, anything
+/// else becomes a generic storage error.
+impl From<anyhow::Error> for Error {
+    fn from(error: anyhow::Error) -> Self {
+        // "context: cause: root cause", without backtrace
+        let msg = format!("{:#}", error);
+
+        let error = match error.downcast::<Error>() {
+            Ok(error) => return error,
+            Err(error) => error,
+        };
+
+        match error.downcast::<io::Error>() {
+            Ok(source) => Error::Io { msg, source },
+            Err(_) => Error::Storage { msg },
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Self {
+        Error::Io {
+            msg: source.to_string(),
+            source,
         }
     }
 }
 
 //
-// Helper Functions ---------------------
+// Tests ---------------------------
 //
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{bail, Context};
+
+    #[test]
+    fn from_anyhow() {
+        let error: Error = anyhow::Error::new(io::Error::from(io::ErrorKind::NotFound))
+            .context("Unable to read file x.txt")
+            .into();
+        assert_eq!(4, error.exit_code());
+        assert!(
+            matches!(error, Error::Io { ref source, .. } if source.kind() == io::ErrorKind::NotFound)
+        );
+        assert!(error.to_string().starts_with("Unable to read file x.txt: "));
+
+        let typed =
+            || -> anyhow::Result<()> { bail!(Error::parse("x.txt", Some(3), "Invalid weight")) };
+        let error: Error = typed().context("Reading categories").unwrap_err().into();
+        assert_eq!("x.txt, line 3: Invalid weight", error.to_string());
+        assert_eq!(5, error.exit_code());
+
+        let error: Error = anyhow::anyhow!("Something else").into();
+        assert!(matches!(error, Error::Storage { .. }));
+    }
+
+    #[test]
+    fn exit_codes() {
+        let errors = [
+            Error::usage("x"),
+            Error::UnknownCategory {
+                name: "x".to_string(),
+                suggestion: None,
+            },
+            Error::from(io::Error::from(io::ErrorKind::NotFound)),
+            Error::parse("x.txt", None, "x"),
+            Error::conflict("x"),
+            Error::validation("x"),
+            Error::behind("x"),
+            Error::problems_remaining("x"),
+        ];
+        let codes: Vec<i32> = errors.iter().map(|e| e.exit_code()).collect();
+        assert_eq!(vec![2, 3, 4, 5, 6, 7, 8, 9], codes);
+    }
+}
@@ -0,0 +1,357 @@
+use crate::error::Error;
+use crate::model::{Activity, Config};
+use crate::storage::{self, Storage};
+use chrono::{Date, Local};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Run after every logged activity
+pub const ON_LOG: &str = "on_log";
+/// Run when a logged activity completes the daily goal of its category
+pub const ON_GOAL_REACHED: &str = "on_goal_reached";
+/// Run when a logged activity makes the day the best day of its category so far
+pub const ON_RECORD: &str = "on_record";
+
+/// A just logged activity, with the context hooks get about it
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEvent {
+    pub activity: Activity,
+    /// The day the activity counts for
+    pub day: Date<Local>,
+    /// Repetitions of the category on that day, including the activity
+    pub day_reps: u32,
+    /// Daily goal of the category, if any
+    pub goal: Option<u32>,
+    /// Most repetitions of the category on any earlier day. Only determined if an on_record hook
+    /// exists, as it needs all activities.
+    pub previous_record: Option<u32>,
+}
+
+impl LogEvent {
+    /// Whether the activity completed the daily goal, i.e. the goal was not reached before it
+    pub fn goal_reached(&self) -> bool {
+        match self.goal {
+            Some(goal) => self.reps_before() < goal && self.day_reps >= goal,
+            None => false,
+        }
+    }
+
+    /// Whether the activity pushed the day above the previous record
+    pub fn is_record(&self) -> bool {
+        match self.previous_record {
+            Some(record) => self.reps_before() <= record && self.day_reps > record,
+            None => false,
+        }
+    }
+
+    /// The names of the hooks to run for this event
+    pub fn hooks(&self) -> Vec<&'static str> {
+        let mut hooks = vec![ON_LOG];
+        if self.goal_reached() {
+            hooks.push(ON_GOAL_REACHED);
+        }
+        if self.is_record() {
+            hooks.push(ON_RECORD);
+        }
+
+        hooks
+    }
+
+    /// All details as JSON object, for the stdin of hooks
+    pub fn to_json(&self, hook: &str) -> String {
+        let mut fields = vec![
+            format!("\"hook\":{}", json_string(hook)),
+            format!("\"category\":{}", json_string(&self.activity.category)),
+            format!("\"reps\":{}", self.activity.reps),
+            format!(
+                "\"timestamp\":{}",
+                json_string(&self.activity.timestamp.to_rfc3339())
+            ),
+            format!(
+                "\"day\":{}",
+                json_string(&self.day.format("%Y-%m-%d").to_string())
+            ),
+            format!("\"day_reps\":{}", self.day_reps),
+        ];
+        if let Some(goal) = self.goal {
+            fields.push(format!("\"goal\":{}", goal));
+        }
+        if let Some(record) = self.previous_record {
+            fields.push(format!("\"previous_record\":{}", record));
+        }
+
+        format!("{{{}}}", fields.join(","))
+    }
+
+    /// All details as environment variables for hooks
+    pub fn env_vars(&self, hook: &str) -> Vec<(&'static str, String)> {
+        let mut vars = vec![
+            ("NADAY_HOOK", hook.to_string()),
+            ("NADAY_CATEGORY", self.activity.category.to_string()),
+            ("NADAY_REPS", self.activity.reps.to_string()),
+            ("NADAY_TIMESTAMP", self.activity.timestamp.to_rfc3339()),
+            ("NADAY_DAY", self.day.format("%Y-%m-%d").to_string()),
+            ("NADAY_DAY_REPS", self.day_reps.to_string()),
+        ];
+        if let Some(goal) = self.goal {
+            vars.push(("NADAY_GOAL", goal.to_string()));
+        }
+        if let Some(record) = self.previous_record {
+            vars.push(("NADAY_PREVIOUS_RECORD", record.to_string()));
+        }
+
+        vars
+    }
+
+    fn reps_before(&self) -> u32 {
+        self.day_reps.saturating_sub(self.activity.reps)
+    }
+}
+
+/// Run the hooks for an activity that was just stored. Hooks are executables in the 'hooks'
+/// subdirectory of the data directory, named after the event. They get the details in
+/// environment variables and as JSON object on stdin.
+///
+/// # Returns
+/// Descriptions of hooks that could not be run or failed
+pub fn after_log(
+    activity: &Activity,
+    storage: &dyn Storage,
+    config: &Config,
+) -> Result<Vec<String>, Error> {
+    if !hooks_dir(config).is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let with_record = hook_path(config, ON_RECORD).is_some();
+    let event = log_event(activity, with_record, storage, config)?;
+
+    let mut problems = Vec::new();
+    for hook in event.hooks() {
+        if let Some(path) = hook_path(config, hook) {
+            if let Err(problem) = run_hook(&path, hook, &event) {
+                problems.push(problem);
+            }
+        }
+    }
+
+    Ok(problems)
+}
+
+//
+// Internals ---------------------------
+//
+
+/// Collect the context of a just stored activity
+fn log_event(
+    activity: &Activity,
+    with_record: bool,
+    storage: &dyn Storage,
+    config: &Config,
+) -> Result<LogEvent, Error> {
+    let day = config.day_of(&activity.timestamp);
+    let is_same_category = |a: &Activity| a.category == activity.category;
+
+    let day_reps = storage
+        .read_days(&day, &day)?
+        .iter()
+        .filter(|a| is_same_category(a) && config.day_of(&a.timestamp) == day)
+        .map(|a| a.reps)
+        .sum();
+
+    let plan = storage::read_plan(config)?;
+    let goal = storage
+        .read_categories()?
+        .targets_on(day.naive_local(), plan.as_ref())
+        .remove(&activity.category);
+
+    let previous_record = if with_record {
+        let mut by_day: HashMap<Date<Local>, u32> = HashMap::new();
+        for earlier in storage.read_all()?.iter().filter(|a| is_same_category(a)) {
+            let earlier_day = config.day_of(&earlier.timestamp);
+            if earlier_day < day {
+                *by_day.entry(earlier_day).or_insert(0) += earlier.reps;
+            }
+        }
+        by_day.values().max().copied()
+    } else {
+        None
+    };
+
+    Ok(LogEvent {
+        activity: activity.clone(),
+        day,
+        day_reps,
+        goal,
+        previous_record,
+    })
+}
+
+fn hooks_dir(config: &Config) -> PathBuf {
+    Path::new(&config.data_dir).join("hooks")
+}
+
+/// The path of the given hook, if it exists
+fn hook_path(config: &Config, hook: &str) -> Option<PathBuf> {
+    let path = hooks_dir(config).join(hook);
+    if path.is_file() {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+fn run_hook(path: &Path, hook: &str, event: &LogEvent) -> Result<(), String> {
+    let mut child = Command::new(path)
+        .envs(event.env_vars(hook))
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("Unable to run hook {:?}: {}", path, error))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // hooks don't have to read the details, so a closed pipe is fine
+        let _ = writeln!(stdin, "{}", event.to_json(hook));
+    }
+
+    match child.wait() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("Hook {:?} failed with {}", path, status)),
+        Err(error) => Err(format!("Unable to run hook {:?}: {}", path, error)),
+    }
+}
+
+/// Quote the given string for JSON
+fn json_string(s: &str) -> String {
+    let mut result = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+
+    result
+}
+
+//
+// Tests ---------------------------------------------------
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Category;
+    use crate::storage::memory::MemoryStorage;
+    use chrono::{DateTime, TimeZone};
+
+    #[test]
+    fn events() {
+        let mut pushups = Category::new("Pushups", 1.0, vec!["pu"]);
+        pushups.goal = Some(50);
+        let storage = MemoryStorage::new(vec![pushups, Category::new("Burpees", 1.5, vec!["bu"])]);
+        let config = Config::new("/nonexistent");
+
+        let day = Local.ymd(2021, 3, 14);
+        store(&storage, day.pred().and_hms(9, 0, 0), 30, "Pushups");
+        store(&storage, day.pred().and_hms(19, 0, 0), 30, "Pushups");
+        store(&storage, day.pred().and_hms(19, 0, 0), 80, "Burpees");
+
+        let first = store(&storage, day.and_hms(9, 0, 0), 40, "Pushups");
+        let event = log_event(&first, true, &storage, &config).unwrap();
+        assert_eq!((40, Some(50), Some(60)), summary(&event));
+        assert_eq!(vec![ON_LOG], event.hooks());
+
+        let second = store(&storage, day.and_hms(12, 0, 0), 15, "Pushups");
+        let event = log_event(&second, true, &storage, &config).unwrap();
+        assert_eq!((55, Some(50), Some(60)), summary(&event));
+        assert_eq!(vec![ON_LOG, ON_GOAL_REACHED], event.hooks());
+
+        let third = store(&storage, day.and_hms(18, 0, 0), 10, "Pushups");
+        let event = log_event(&third, true, &storage, &config).unwrap();
+        assert_eq!(vec![ON_LOG, ON_RECORD], event.hooks());
+
+        // no record without earlier days, and no goal for Burpees
+        let burpees = store(&storage, day.pred().pred().and_hms(8, 0, 0), 10, "Burpees");
+        let event = log_event(&burpees, true, &storage, &config).unwrap();
+        assert_eq!((10, None, None), summary(&event));
+        assert_eq!(vec![ON_LOG], event.hooks());
+
+        let event = log_event(&third, false, &storage, &config).unwrap();
+        assert_eq!(None, event.previous_record);
+        assert_eq!(
+            format!(
+                "{{\"hook\":\"on_log\",\"category\":\"Pushups\",\"reps\":10,\"timestamp\":\"{}\",\
+                 \"day\":\"2021-03-14\",\"day_reps\":65,\"goal\":50}}",
+                third.timestamp.to_rfc3339()
+            ),
+            event.to_json(ON_LOG)
+        );
+        assert_eq!("\"a\\\"b\\\\c\\u000a\"", json_string("a\"b\\c\n"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_hooks() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+        use tempfile::TempDir;
+
+        let tmp = TempDir::new().unwrap();
+        let config = Config::new(tmp.path().to_str().unwrap());
+        let storage = MemoryStorage::new(vec![Category::new("Pushups", 1.0, vec!["pu"])]);
+        let activity = store(&storage, Local::now(), 20, "Pushups");
+
+        // nothing happens without hooks
+        assert!(after_log(&activity, &storage, &config).unwrap().is_empty());
+
+        fs::create_dir(hooks_dir(&config)).unwrap();
+        let output = tmp.path().join("output.txt");
+        let script = format!(
+            "#!/bin/sh\necho \"$NADAY_HOOK $NADAY_CATEGORY $NADAY_REPS\" > {:?}\ncat >> {:?}\n",
+            output, output
+        );
+        let hook = hooks_dir(&config).join(ON_LOG);
+        fs::write(&hook, script).unwrap();
+        fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+        let failing = hooks_dir(&config).join(ON_RECORD);
+        fs::write(&failing, "#!/bin/sh\nexit 1\n").unwrap();
+        fs::set_permissions(&failing, fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert!(after_log(&activity, &storage, &config).unwrap().is_empty());
+        let contents = fs::read_to_string(&output).unwrap();
+        assert!(contents.starts_with("on_log Pushups 20\n{\"hook\":\"on_log\""));
+
+        // the failing hook runs once there is a record to beat
+        let mut earlier = activity.clone();
+        earlier.timestamp = earlier.timestamp - chrono::Duration::days(2);
+        earlier.reps = 10;
+        storage.store(&earlier).unwrap();
+        let problems = after_log(&activity, &storage, &config).unwrap();
+        assert_eq!(1, problems.len());
+        assert!(problems[0].contains("on_record"));
+    }
+
+    fn summary(event: &LogEvent) -> (u32, Option<u32>, Option<u32>) {
+        (event.day_reps, event.goal, event.previous_record)
+    }
+
+    fn store(
+        storage: &MemoryStorage,
+        timestamp: DateTime<Local>,
+        reps: u32,
+        category: &str,
+    ) -> Activity {
+        let activity = Activity {
+            timestamp,
+            reps,
+            category: category.to_string(),
+        };
+        storage.store(&activity).unwrap();
+        activity
+    }
+}
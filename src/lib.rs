@@ -1,85 +1,575 @@
+//! Tracking of (physical) exercise of the "N repetitions a day" variant.
+//!
+//! Besides the `naday` command line tool, this crate can be embedded in other frontends via the
+//! [`Naday`] handle. Its methods return all results as data, only unreadable lines skipped while
+//! reading data files are reported on stderr.
+
+mod api;
+mod challenge;
+mod check;
 mod cli;
 mod error;
+mod hooks;
 mod model;
+mod remind;
 mod report;
 mod storage;
 
+pub use api::Naday;
+pub use challenge::{ChallengeDay, ChallengeState, ChallengeStatus};
+pub use check::{Finding, Problem};
+pub use error::Error;
+pub use model::{
+    Activity, Category, CategoryMatching, Challenge, ChallengeTarget, Config, Increase, Pace, Plan,
+    PlanSchedule, Schedule, StorageBackend,
+};
+pub use remind::GoalProgress;
+pub use report::{Adherence, DayReport, DayTotals, PlanDay, PlanEntry, PlanReport, RangeReport};
+pub use storage::Backup;
+
 use cli::CliAction;
 use cli::RunContext;
 use itertools::Itertools;
-use model::{Activity, Config};
 use std::cmp::Ord;
 use std::env;
+use std::io;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::Path;
 use std::process;
 
-pub fn cli_parse(args: env::Args) -> RunContext {
-    match RunContext::new(args) {
-        Ok(ctx) => ctx,
-        Err(msg) => {
-            eprintln!("{}", msg);
-            std::process::exit(1);
-        }
-    }
+/// Parse the command line. Errors map to an exit code via [`Error::exit_code`].
+pub fn cli_parse(args: env::Args) -> Result<RunContext, Error> {
+    RunContext::new(args)
 }
 
-pub fn run(ctx: RunContext) -> Result<(), String> {
+/// Execute the parsed command. Results go to stdout, diagnostics and prompts to stderr; nothing
+/// in here terminates the process.
+pub fn run(ctx: RunContext) -> Result<(), Error> {
+    let config = ctx.config;
+
     match ctx.action {
+        CliAction::Info { text } => {
+            // also terminates the version line clap has already printed
+            println!("{}", text);
+            Ok(())
+        }
+        CliAction::Completions { shell } => {
+            print!("{}", cli::completion_script(&shell));
+            Ok(())
+        }
         CliAction::Report {
             kind,
             category,
             sliding: _,
-        } => match kind {
-            cli::ReportKind::Day => report::today(&ctx.config),
-            cli::ReportKind::Week => report::sliding_week(category, &ctx.config),
-            cli::ReportKind::Month => report::sliding_month(category, &ctx.config),
-        },
-        CliAction::System => run_system(&ctx.config),
+        } => run_report(kind, category, &open(config)?),
+        CliAction::System => run_system(&open(config)?),
+        CliAction::Migrate { to } => run_migrate(to, open(config)?),
+        CliAction::MergeConflicts => run_merge_conflicts(open(config)?.config()),
+        CliAction::Upgrade => run_upgrade(&open(config)?),
+        CliAction::Check { fix } => run_check(fix, &open(config)?),
+        CliAction::Backup { action } => run_backup(action, &open(config)?),
+        CliAction::Remind => run_remind(&open(config)?),
+        CliAction::Challenge { action } => run_challenge(action, &open(config)?),
+        CliAction::Plan { action } => run_plan(action, &open(config)?),
+        CliAction::TeamReport {
+            members,
+            categories,
+            days,
+        } => run_team_report(members, categories, days, open(config)?.config()),
+        CliAction::CompleteCategories => run_complete_categories(&open(config)?),
         CliAction::AddActivity {
             repetitions,
             category,
-        } => run_add_activity(repetitions, category, &ctx.config),
+            create,
+            alias,
+        } => run_add_activity(repetitions, category, create, alias, &open(config)?),
     }
 }
 
+/// Open the data directory with the settings of its config file applied
+fn open(mut config: Config) -> Result<Naday, Error> {
+    storage::read_config(&mut config)?;
+    Naday::open(config)
+}
+
 //
 // Main Command handlers ----------------------------
 //
 
-fn run_system(config: &Config) -> Result<(), String> {
-    let categories = storage::read_categories(config)?;
+fn run_report(kind: cli::ReportKind, category: Option<String>, naday: &Naday) -> Result<(), Error> {
+    let storage = naday.storage();
+    let config = naday.config();
+
+    match kind {
+        cli::ReportKind::Day => report::today(storage, config),
+        cli::ReportKind::Week => report::sliding(7, category, storage, config),
+        cli::ReportKind::Month => report::sliding(31, category, storage, config),
+        cli::ReportKind::Year => report::sliding(365, category, storage, config),
+        cli::ReportKind::Days(days) => report::sliding(days, category, storage, config),
+        cli::ReportKind::Plan => run_plan_report(naday),
+    }
+}
+
+fn run_system(naday: &Naday) -> Result<(), Error> {
+    let categories = naday.categories()?;
 
-    println!("Storage directory: {}", &config.data_dir);
+    println!("Storage directory: {}", &naday.config().data_dir);
     println!("Known Categories:");
     for category in categories
         .iter()
         .sorted_by(|a, b| Ord::cmp(&a.name, &b.name))
     {
         println!(
-            "  {:<15} (weight {:<5}), aliases {}",
+            "  {:<15} (weight {:<5}), aliases {}{}",
             &category.name,
-            &category.weight,
-            category.aliases.iter().join(", ")
+            category.current_weight(naday.config()),
+            category.aliases.iter().join(", "),
+            if category.archived { " [archived]" } else { "" }
+        );
+    }
+
+    Ok(())
+}
+
+fn run_migrate(to: StorageBackend, naday: Naday) -> Result<(), Error> {
+    let from = naday.config().storage_backend;
+    let (categories, activities) = naday.migrate(to)?;
+
+    println!(
+        "Migrated {} categories and {} activities from the {} to the {} backend",
+        categories, activities, from, to
+    );
+    Ok(())
+}
+
+fn run_check(fix: bool, naday: &Naday) -> Result<(), Error> {
+    let findings = naday.check(fix)?;
+    for finding in &findings {
+        println!("{}", finding);
+    }
+
+    let remaining = findings.iter().filter(|f| !f.fixed).count();
+    println!(
+        "{} problems found, {} fixed",
+        findings.len(),
+        findings.len() - remaining
+    );
+
+    if remaining > 0 {
+        let hint = if fix {
+            "please repair them manually"
+        } else {
+            "run 'naday check --fix' to repair what can be repaired automatically"
+        };
+        return Err(Error::problems_remaining(format!(
+            "{} problems remaining - {}",
+            remaining, hint
+        )));
+    }
+
+    Ok(())
+}
+
+fn run_remind(naday: &Naday) -> Result<(), Error> {
+    let progress = naday.goal_progress(&chrono::Local::now())?;
+    if progress.is_empty() {
+        println!("No daily goals set - add 'goal=<reps>' to categories in categories.txt");
+        return Ok(());
+    }
+
+    for goal in &progress {
+        println!("{}", goal);
+    }
+
+    let behind: Vec<&GoalProgress> = progress.iter().filter(|g| g.is_behind()).collect();
+    if behind.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(command) = &naday.config().remind_command {
+        let message = behind.iter().join("\n");
+        // a failing notification must not hide the reminder itself
+        match process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("NADAY_MESSAGE", &message)
+            .status()
+        {
+            Ok(status) if !status.success() => {
+                eprintln!("Remind command '{}' failed with {}", command, status)
+            }
+            Err(error) => eprintln!("Unable to run remind command '{}': {}", command, error),
+            Ok(_) => {}
+        }
+    }
+
+    Err(Error::behind(format!(
+        "Behind on {} of {} daily goals",
+        behind.len(),
+        progress.len()
+    )))
+}
+
+fn run_challenge(action: cli::ChallengeAction, naday: &Naday) -> Result<(), Error> {
+    let config = naday.config();
+    let today = config.today();
+
+    match action {
+        cli::ChallengeAction::List => {
+            let challenges = naday.challenges()?;
+            if challenges.is_empty() {
+                println!("No challenges yet - add one with 'naday challenge add'");
+            }
+            for challenge in challenges {
+                let status = naday.challenge_status(&challenge, &today)?;
+                println!("{}", challenge::format_summary(&status, config));
+            }
+        }
+        cli::ChallengeAction::Add {
+            name,
+            category,
+            target,
+            start,
+            end,
+            days,
+        } => {
+            let start = start.unwrap_or_else(|| today.naive_local());
+            let end = match (end, days) {
+                (Some(end), _) => end,
+                (None, Some(days)) => start + chrono::Duration::days(i64::from(days) - 1),
+                (None, None) => start,
+            };
+            let challenge = naday.add_challenge(Challenge {
+                name,
+                category,
+                start,
+                end,
+                target,
+            })?;
+            let status = naday.challenge_status(&challenge, &today)?;
+            println!(
+                "Added challenge {}",
+                challenge::format_summary(&status, config)
+            );
+        }
+        cli::ChallengeAction::Status { name } => {
+            // without a name, only the running challenges are of interest
+            let only_running = name.is_none();
+            let challenges = match name {
+                Some(name) => vec![naday.find_challenge(&name)?],
+                None => naday.challenges()?,
+            };
+
+            let mut shown = 0;
+            for challenge in challenges {
+                let status = naday.challenge_status(&challenge, &today)?;
+                if status.state == ChallengeState::Running || !only_running {
+                    if shown > 0 {
+                        println!();
+                    }
+                    print!("{}", challenge::format_details(&status, config));
+                    shown += 1;
+                }
+            }
+            if shown == 0 {
+                println!("No running challenges - see 'naday challenge list' for all");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn run_plan(action: cli::PlanAction, naday: &Naday) -> Result<(), Error> {
+    match action {
+        cli::PlanAction::Load { file } => {
+            let plan = naday.load_plan(naday.read_plan_file(Path::new(&file))?)?;
+            println!(
+                "Loaded training plan from {} to {} ({} days) for {}",
+                plan.start.format(&naday.config().date_format),
+                plan.end().format(&naday.config().date_format),
+                plan.days(),
+                plan.schedules.iter().map(|s| &s.category).join(", ")
+            );
+        }
+        cli::PlanAction::Clear => {
+            naday.clear_plan()?;
+            println!("Removed the training plan - the daily goals apply again");
+        }
+    }
+
+    Ok(())
+}
+
+fn run_plan_report(naday: &Naday) -> Result<(), Error> {
+    match naday.plan()? {
+        Some(plan) => report::plan(&plan, naday.storage(), naday.config()),
+        None => Err(Error::validation(
+            "No training plan loaded - load one with 'naday plan load <FILE>'",
+        )),
+    }
+}
+
+fn run_team_report(
+    members: Vec<(String, String)>,
+    categories: Option<String>,
+    days: u32,
+    config: &Config,
+) -> Result<(), Error> {
+    let mut member_data = Vec::new();
+    for (name, data_dir) in members {
+        if !Path::new(&data_dir).is_dir() {
+            return Err(Error::validation(format!(
+                "Data directory of {} not found: {}",
+                name, data_dir
+            )));
+        }
+
+        // never write to the directories of others
+        let mut member_config = Config::new(&data_dir);
+        storage::read_config_read_only(&mut member_config)?;
+        let member_storage = storage::open_read_only(&member_config)?;
+        member_data.push((name, member_config, member_storage));
+    }
+
+    let shared_categories = match categories {
+        Some(file) => Some(storage::read_category_file(Path::new(&file))?),
+        None => None,
+    };
+
+    let members: Vec<report::Member> = member_data
+        .iter()
+        .map(|(name, config, storage)| report::Member {
+            name: name.to_string(),
+            storage: storage.as_ref(),
+            config,
+        })
+        .collect();
+    report::team(days, &members, shared_categories.as_ref(), config)
+}
+
+fn run_complete_categories(naday: &Naday) -> Result<(), Error> {
+    for category in naday.categories()?.iter().filter(|c| !c.archived) {
+        println!("{}", &category.name);
+        for alias in &category.aliases {
+            println!("{}", alias);
+        }
+    }
+
+    Ok(())
+}
+
+fn run_backup(action: cli::BackupAction, naday: &Naday) -> Result<(), Error> {
+    match action {
+        cli::BackupAction::List => {
+            let backups = naday.backups()?;
+            if backups.is_empty() {
+                println!("No backups yet");
+            }
+            for backup in backups {
+                println!(
+                    "{:<32} {}  {} files",
+                    &backup.id,
+                    backup.created.format("%Y-%m-%d %H:%M:%S"),
+                    backup.files
+                );
+            }
+        }
+        cli::BackupAction::Create => {
+            let backup = naday.create_backup()?;
+            println!("Created backup {} ({} files)", &backup.id, backup.files);
+        }
+        cli::BackupAction::Restore { id } => {
+            let before = naday.restore_backup(&id)?;
+            println!(
+                "Restored backup {} - the previous state is kept as backup {}",
+                &id, &before.id
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn run_merge_conflicts(config: &Config) -> Result<(), Error> {
+    if config.storage_backend != StorageBackend::Text {
+        return Err(Error::usage(
+            "Merging sync conflicts is only supported for the text backend",
+        ));
+    }
+
+    let results = storage::merge_conflicts(config)?;
+    if results.is_empty() {
+        println!("No sync conflicts found");
+    }
+
+    for result in results {
+        println!(
+            "Merged {} entries from {} into {}",
+            result.merged, result.copy, result.target
         );
+        for note in result.notes {
+            eprintln!("  {}", note);
+        }
+    }
+
+    Ok(())
+}
+
+fn run_upgrade(naday: &Naday) -> Result<(), Error> {
+    match naday.upgrade()? {
+        0 => println!("All files are in the current format"),
+        upgraded => println!("Upgraded {} files", upgraded),
     }
 
     Ok(())
 }
 
-fn run_add_activity(repetitions: u32, category: String, config: &Config) -> Result<(), String> {
-    let categories = storage::read_categories(config)?;
+fn run_add_activity(
+    repetitions: u32,
+    category: String,
+    create: bool,
+    alias: Option<String>,
+    naday: &Naday,
+) -> Result<(), Error> {
+    let activity = match naday.log(repetitions, &category) {
+        // an ambiguous prefix or an archived category is not a new category
+        Err(error @ Error::UnknownCategory { .. }) => {
+            if create {
+                create_category(&category, alias, naday)?;
+            } else if io::stdin().is_terminal() {
+                eprintln!("{}", error);
+                match prompt_create_category(&category)? {
+                    Some(alias) => create_category(&category, alias, naday)?,
+                    None => return Err(error),
+                }
+            } else {
+                return Err(error);
+            }
 
-    let category = match categories.find(&category) {
-        Some(cat) => cat.name.to_string(),
-        None => {
-            eprintln!("Activity category '{}' is not known", category);
-            process::exit(1);
+            naday.log(repetitions, &category)?
         }
+        result => result?,
     };
 
-    let activity = Activity::new(repetitions, &category);
-    storage::store(&activity, config)?;
+    println!("Added {} {}", activity.reps, &activity.category);
+    report::today(naday.storage(), naday.config())?;
+
+    // the activity is stored, so failing hooks are no reason to fail
+    match hooks::after_log(&activity, naday.storage(), naday.config()) {
+        Ok(problems) => problems.iter().for_each(|problem| eprintln!("{}", problem)),
+        Err(error) => eprintln!("Unable to run hooks: {}", error),
+    }
+    Ok(())
+}
+
+//
+// Helpers ----------------------------------
+//
+
+/// Add a new category with weight 1 and the given alias
+fn create_category(name: &str, alias: Option<String>, naday: &Naday) -> Result<(), Error> {
+    let category = Category::new(name, 1.0, alias.into_iter().collect());
+    naday.add_category(category.clone())?;
 
-    println!("Added {} {}", repetitions, &category);
-    report::today(config)?;
+    println!(
+        "Created category {} (weight {}, aliases {})",
+        &category.name,
+        &category.weight,
+        category.aliases.iter().join(", ")
+    );
     Ok(())
 }
+
+/// Interactively ask whether an unknown category should be created.
+///
+/// # Returns
+/// `None` if the category should not be created, otherwise the (optional) alias for it
+fn prompt_create_category(name: &str) -> Result<Option<Option<String>>, Error> {
+    if !prompt_yes_no(&format!("Create category '{}' with weight 1?", name))? {
+        return Ok(None);
+    }
+
+    let alias = prompt(&format!("Alias for '{}' (leave empty for none): ", name))?;
+    if alias.is_empty() {
+        Ok(Some(None))
+    } else {
+        Ok(Some(Some(alias)))
+    }
+}
+
+fn prompt_yes_no(question: &str) -> Result<bool, Error> {
+    let answer = prompt(&format!("{} [y/N] ", question))?;
+    Ok(answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes"))
+}
+
+/// Print the given text to stderr and read one (trimmed) line from stdin
+fn prompt(text: &str) -> Result<String, Error> {
+    eprint!("{}", text);
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+
+    Ok(answer.trim().to_string())
+}
+
+//
+// Tests ---------------------------------
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    #[test]
+    fn team_report_read_only() {
+        let own = TempDir::new().unwrap();
+        let member = TempDir::new().unwrap();
+        // outdated formats, no config file
+        fs::write(
+            member.path().join("categories.txt"),
+            "naday categories v1\nPushups;1;pu\n",
+        )
+        .unwrap();
+        fs::write(
+            member.path().join("2021-03.txt"),
+            "naday activities v1\n2021-03-01 10:00:00;10;Pushups\n",
+        )
+        .unwrap();
+        let before = snapshot(member.path());
+
+        let ctx = RunContext {
+            config: Config::new(own.path().to_str().unwrap()),
+            action: CliAction::TeamReport {
+                members: vec![(
+                    "alice".to_string(),
+                    member.path().to_str().unwrap().to_string(),
+                )],
+                categories: None,
+                days: 7,
+            },
+        };
+        run(ctx).unwrap();
+
+        assert_eq!(before, snapshot(member.path()));
+    }
+
+    /// All files below the given directory with their contents
+    fn snapshot(dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+        let mut files = BTreeMap::new();
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(snapshot(&path));
+            } else {
+                files.insert(path.clone(), fs::read(&path).unwrap());
+            }
+        }
+
+        files
+    }
+}
@@ -1,17 +1,19 @@
 use human_panic::setup_panic;
 use std::env;
+use std::process;
 
+// setup_panic! still uses the deprecated PanicInfo alias
+#[allow(deprecated)]
 fn main() {
     setup_panic!();
 
-    let ctx = naday::cli_parse(env::args());
-
     env_logger::builder()
         .format_timestamp(None)
         .format_module_path(false)
         .init();
 
-    if let Err(msg) = naday::run(ctx) {
-        eprintln!("Error: {}", msg);
+    if let Err(error) = naday::cli_parse(env::args()).and_then(naday::run) {
+        eprintln!("Error: {}", error);
+        process::exit(error.exit_code());
     }
 }
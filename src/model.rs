@@ -1,25 +1,232 @@
-use chrono::{DateTime, Local};
+use anyhow::{bail, Result};
+use chrono::naive::MAX_DATE;
+use chrono::{Date, DateTime, Duration, Local, NaiveDate, NaiveTime, TimeZone, Weekday};
+use chrono_tz::Tz;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::convert::TryFrom;
 use std::fmt;
 use std::fmt::Display;
+use std::str::FromStr;
 
 mod category_lookup;
 
 pub use category_lookup::CategoryLookup;
 
+lazy_static! {
+    static ref NAME_PATTERN: Regex = Regex::new(r"^[a-zA-Z_]\w*$").unwrap();
+}
+
 //
 // Config -------------------------
 //
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Config {
     pub data_dir: String,
+    pub category_matching: CategoryMatching,
+    pub storage_backend: StorageBackend,
+    /// Create a backup of the data directory once per day
+    pub daily_backup: bool,
+    /// Number of automatic backups to keep. Backups created on request are never deleted.
+    pub backups_to_keep: usize,
+    /// Time zone whose calendar days activities are counted for. None means the system time zone.
+    pub home_timezone: Option<Tz>,
+    /// Hour (0-23) at which a new day starts. Activities before it count for the previous day.
+    pub day_starts_at: u32,
+    /// First day of the week, for separating weeks in reports
+    pub week_starts_on: Weekday,
+    /// Format (strftime syntax) of dates in reports
+    pub date_format: String,
+    /// Share of the daily goals that should be reached at each time of day
+    pub pace: Pace,
+    /// Shell command run by 'naday remind' when behind schedule, with the message in the
+    /// environment variable NADAY_MESSAGE
+    pub remind_command: Option<String>,
+}
+
+impl Config {
+    /// Create a config for the given data directory, with all settings at their defaults
+    pub fn new<S: AsRef<str>>(data_dir: S) -> Config {
+        Config {
+            data_dir: data_dir.as_ref().to_string(),
+            category_matching: CategoryMatching::Prefix,
+            storage_backend: StorageBackend::Text,
+            daily_backup: false,
+            backups_to_keep: 10,
+            home_timezone: None,
+            day_starts_at: 0,
+            week_starts_on: Weekday::Mon,
+            date_format: "%Y-%m-%d".to_string(),
+            pace: Pace::default(),
+            remind_command: None,
+        }
+    }
+
+    /// The day the given timestamp counts for: Its date in the home time zone, or the date before
+    /// if it is earlier than the configured start of day
+    pub fn day_of(&self, timestamp: &DateTime<Local>) -> Date<Local> {
+        let local_time = match self.home_timezone {
+            Some(tz) => timestamp.with_timezone(&tz).naive_local(),
+            None => timestamp.naive_local(),
+        };
+        let date = (local_time - Duration::hours(self.day_starts_at.into())).date();
+
+        Local
+            .from_local_date(&date)
+            .earliest()
+            .unwrap_or_else(|| timestamp.date())
+    }
+
+    /// The current day in the home time zone
+    pub fn today(&self) -> Date<Local> {
+        self.day_of(&Local::now())
+    }
+
+    /// The time of day of the given timestamp in the home time zone
+    pub fn time_of_day(&self, timestamp: &DateTime<Local>) -> NaiveTime {
+        match self.home_timezone {
+            Some(tz) => timestamp.with_timezone(&tz).time(),
+            None => timestamp.time(),
+        }
+    }
+
+    /// The time of day at which a new day starts
+    pub fn day_start(&self) -> NaiveTime {
+        NaiveTime::from_hms(self.day_starts_at, 0, 0)
+    }
+}
+
+/// Pace model for reaching the daily goals: Checkpoints stating which share of the goal should be
+/// reached by which time, e.g. 75% by 18:00. Between the start of the day (0%) and the checkpoints
+/// the expected share grows linearly.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Pace {
+    /// Times of day and percentages
+    checkpoints: Vec<(NaiveTime, u32)>,
+}
+
+impl Pace {
+    /// The percentage of the goal that should be reached at the given time of a day starting at
+    /// `day_start`
+    pub fn expected_percent(&self, time: NaiveTime, day_start: NaiveTime) -> u32 {
+        // minutes since the start of the day, which may be after midnight
+        let offset = |t: NaiveTime| (t - day_start).num_minutes().rem_euclid(24 * 60);
+        let now = offset(time);
+        let mut checkpoints: Vec<(i64, i64)> = self
+            .checkpoints
+            .iter()
+            .map(|(time, percent)| (offset(*time), *percent as i64))
+            .collect();
+        checkpoints.sort_unstable();
+
+        let mut previous = (0, 0);
+        for (at, percent) in checkpoints {
+            if now < at {
+                let (prev_at, prev_percent) = previous;
+                let progress = (now - prev_at) as f64 / (at - prev_at) as f64;
+                return (prev_percent as f64 + progress * (percent - prev_percent) as f64) as u32;
+            }
+            previous = (at, percent);
+        }
+
+        previous.1 as u32
+    }
+}
+
+impl Default for Pace {
+    fn default() -> Pace {
+        "12:00=30,18:00=75,21:00=100".parse().unwrap()
+    }
+}
+
+impl FromStr for Pace {
+    type Err = anyhow::Error;
+
+    /// Parse a list of checkpoints like '12:00=30,18:00=75,21:00=100'
+    fn from_str(s: &str) -> Result<Self> {
+        let mut checkpoints = Vec::new();
+        for checkpoint in s.split(',') {
+            let parsed = checkpoint.split_once('=').and_then(|(time, percent)| {
+                let time = NaiveTime::parse_from_str(time.trim(), "%H:%M").ok()?;
+                let percent: u32 = percent.trim().trim_end_matches('%').parse().ok()?;
+                Some((time, percent))
+            });
+            match parsed {
+                Some((time, percent)) if percent <= 100 => checkpoints.push((time, percent)),
+                _ => bail!(
+                    "Invalid pace checkpoint <{}>, expected e.g. '18:00=75'",
+                    checkpoint.trim()
+                ),
+            }
+        }
+
+        Ok(Pace { checkpoints })
+    }
+}
+
+/// The available backends for storing activities and categories
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StorageBackend {
+    /// Plain text files in the data directory, one per month
+    Text,
+    /// A single SQLite database in the data directory (needs the 'sqlite' cargo feature)
+    Sqlite,
+}
+
+impl Display for StorageBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageBackend::Text => write!(f, "text"),
+            StorageBackend::Sqlite => write!(f, "sqlite"),
+        }
+    }
+}
+
+impl FromStr for StorageBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "text" => Ok(StorageBackend::Text),
+            "sqlite" => Ok(StorageBackend::Sqlite),
+            _ => bail!(
+                "Unknown storage backend <{}>, expected 'text' or 'sqlite'",
+                s
+            ),
+        }
+    }
+}
+
+/// How category names and aliases given by the user are matched against the known categories
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CategoryMatching {
+    /// Only accept a (case insensitive) full name or alias
+    Exact,
+    /// Additionally accept an unambiguous prefix of a name or alias
+    Prefix,
+}
+
+impl FromStr for CategoryMatching {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "exact" => Ok(CategoryMatching::Exact),
+            "prefix" => Ok(CategoryMatching::Prefix),
+            _ => bail!(
+                "Unknown category matching mode <{}>, expected 'exact' or 'prefix'",
+                s
+            ),
+        }
+    }
 }
 
 //
 // Activity -----------------------
 //
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Activity {
     pub timestamp: DateTime<Local>,
     pub category: String,
@@ -50,11 +257,19 @@ impl PartialEq for Activity {
 // Category ----------------------------
 //
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Category {
     pub name: String,
     pub aliases: Vec<String>,
+    /// Weight of repetitions in relation to other categories. If the weight was changed over time,
+    /// this is the original weight and `weight_changes` holds the later values.
     pub weight: f64,
+    /// Weights valid from the given day on, sorted by day
+    pub weight_changes: Vec<(NaiveDate, f64)>,
+    /// Archived categories can't be used for logging anymore, but are still known to reports
+    pub archived: bool,
+    /// Number of repetitions to reach each day, if any
+    pub goal: Option<u32>,
 }
 
 impl Category {
@@ -63,9 +278,41 @@ impl Category {
             name: name.to_string(),
             weight,
             aliases: aliases.iter().map(|a| a.to_string()).collect(),
+            weight_changes: Vec::new(),
+            archived: false,
+            goal: None,
         }
     }
 
+    /// Record that the weight changed to the given value on the given day
+    pub fn change_weight(&mut self, since: NaiveDate, weight: f64) {
+        self.weight_changes.push((since, weight));
+        self.weight_changes.sort_by_key(|(since, _)| *since);
+    }
+
+    /// Get the weight that was valid on the given day
+    pub fn weight_on(&self, day: &Date<Local>) -> f64 {
+        let day = day.naive_local();
+
+        self.weight_changes
+            .iter()
+            .rev()
+            .find(|(since, _)| *since <= day)
+            .map(|(_, weight)| *weight)
+            .unwrap_or(self.weight)
+    }
+
+    /// Whether the given string can be used as category name or alias in activity specs
+    pub fn is_valid_name(name: &str) -> bool {
+        NAME_PATTERN.is_match(name)
+    }
+
+    /// Get the weight valid today, in the home time zone of the given config
+    pub fn current_weight(&self, config: &Config) -> f64 {
+        self.weight_on(&config.today())
+    }
+
+    #[allow(clippy::needless_borrow)]
     fn all_names(&self) -> Vec<&str> {
         let mut result: Vec<&str> = Vec::new();
         result.push(&self.name);
@@ -81,8 +328,169 @@ impl Display for Category {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Category ({}, {}, {:?})",
+            "Category ({}, {}, {:?}",
             self.name, self.weight, self.aliases
-        )
+        )?;
+        for (since, weight) in &self.weight_changes {
+            write!(f, ", {} since {}", weight, since)?;
+        }
+        if let Some(goal) = self.goal {
+            write!(f, ", goal {}", goal)?;
+        }
+        if self.archived {
+            write!(f, ", archived")?;
+        }
+        write!(f, ")")
+    }
+}
+
+//
+// Challenge ----------------------------
+//
+
+/// A time-boxed target for a category, e.g. 3000 pushups in October
+#[derive(Debug, Clone, PartialEq)]
+pub struct Challenge {
+    pub name: String,
+    pub category: String,
+    /// First day of the challenge
+    pub start: NaiveDate,
+    /// Last day of the challenge (inclusive)
+    pub end: NaiveDate,
+    pub target: ChallengeTarget,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChallengeTarget {
+    /// Repetitions to reach over the whole challenge
+    Total(u32),
+    /// Repetitions to reach on each day of the challenge
+    Daily(u32),
+}
+
+impl Challenge {
+    /// The number of days of the challenge
+    pub fn days(&self) -> u32 {
+        ((self.end - self.start).num_days() + 1).max(0) as u32
+    }
+
+    /// Repetitions to reach each day. For total targets, this is the share of an even
+    /// distribution over all days.
+    pub fn daily_target(&self) -> u32 {
+        match self.target {
+            ChallengeTarget::Total(total) => total.div_ceil(self.days().max(1)),
+            ChallengeTarget::Daily(daily) => daily,
+        }
+    }
+}
+
+impl Display for ChallengeTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChallengeTarget::Total(total) => write!(f, "{} in total", total),
+            ChallengeTarget::Daily(daily) => write!(f, "{} per day", daily),
+        }
+    }
+}
+
+//
+// Training plan ----------------------------
+//
+
+/// A training plan: Daily targets per category that change over time
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan {
+    /// First day of the plan
+    pub start: NaiveDate,
+    pub schedules: Vec<PlanSchedule>,
+}
+
+/// The targets of a single category in a training plan
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanSchedule {
+    pub category: String,
+    pub schedule: Schedule,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schedule {
+    /// A daily target for the first week, raised each following week
+    Weekly {
+        initial: u32,
+        increase: Increase,
+        weeks: u32,
+    },
+    /// An explicit target for each day, 0 for rest days
+    Days(Vec<u32>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Increase {
+    /// Percentage of the previous week's target
+    Percent(u32),
+    Reps(u32),
+}
+
+impl Schedule {
+    /// The number of days the schedule lasts
+    pub fn days(&self) -> u32 {
+        match self {
+            Schedule::Weekly { weeks, .. } => weeks.saturating_mul(7),
+            Schedule::Days(targets) => targets.len() as u32,
+        }
+    }
+
+    /// The target on the given day of the schedule (0 is the first day), if it lasts that long
+    pub fn target(&self, day: u32) -> Option<u32> {
+        if day >= self.days() {
+            return None;
+        }
+
+        match self {
+            Schedule::Weekly {
+                initial, increase, ..
+            } => {
+                let week = day / 7;
+                Some(match increase {
+                    Increase::Percent(percent) => {
+                        let factor = (1.0 + *percent as f64 / 100.0).powi(week as i32);
+                        // saturates at u32::MAX
+                        (*initial as f64 * factor).round() as u32
+                    }
+                    Increase::Reps(reps) => initial.saturating_add(week.saturating_mul(*reps)),
+                })
+            }
+            Schedule::Days(targets) => targets.get(day as usize).copied(),
+        }
+    }
+}
+
+impl Plan {
+    /// The last day of the plan
+    pub fn end(&self) -> NaiveDate {
+        let days = self.schedules.iter().map(|s| s.schedule.days()).max();
+        let duration = Duration::days(i64::from(days.unwrap_or(1).max(1)) - 1);
+        self.start.checked_add_signed(duration).unwrap_or(MAX_DATE)
+    }
+
+    /// The number of days of the plan
+    pub fn days(&self) -> u32 {
+        (self.end() - self.start).num_days() as u32 + 1
+    }
+
+    /// The targets of all categories on the given day, leaving out rest days
+    pub fn targets_on(&self, day: NaiveDate) -> Vec<(String, u32)> {
+        let index = match u32::try_from((day - self.start).num_days()) {
+            Ok(index) => index,
+            Err(_) => return Vec::new(),
+        };
+
+        self.schedules
+            .iter()
+            .filter_map(|s| match s.schedule.target(index) {
+                Some(target) if target > 0 => Some((s.category.to_string(), target)),
+                _ => None,
+            })
+            .collect()
     }
 }
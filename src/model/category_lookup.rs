@@ -1,13 +1,20 @@
-use super::Category;
-use anyhow::{bail, Result};
-use std::collections::HashMap;
+use super::{Category, CategoryMatching, Plan};
+use crate::error::Error;
+use chrono::NaiveDate;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
 use std::rc::Rc;
 
-/// Collection holding all known Category s, plus a method to find a category by its name or alias
+/// Minimum Jaro-Winkler similarity for a name or alias to be offered as suggestion
+const SUGGESTION_THRESHOLD: f64 = 0.7;
+
+/// Collection holding all known Category s, plus a method to find a category by its name or alias.
+/// Archived categories can only be found by their exact name via `get()`.
 #[derive(Debug)]
 pub struct CategoryLookup {
     categories: HashMap<String, Rc<Category>>,
     by_name_or_alias: HashMap<String, Rc<Category>>,
+    in_order: Vec<Rc<Category>>,
 }
 
 impl CategoryLookup {
@@ -15,25 +22,37 @@ impl CategoryLookup {
         CategoryLookup {
             categories: HashMap::new(),
             by_name_or_alias: HashMap::new(),
+            in_order: Vec::new(),
         }
     }
 
     /// Add the given category to the lookup.
     /// Re-adding a category will be silently ignored. Adding a category with a name or alias that
-    /// is already in use will create an Err
-    pub fn add(&mut self, category: Category) -> Result<()> {
+    /// is already in use will create an Err. The aliases of archived categories are not considered
+    /// to be in use.
+    pub fn add(&mut self, category: Category) -> Result<(), Error> {
         if self.categories.contains_key(&category.name.to_lowercase()) {
             return Ok(()); // No-op. Maybe emit a warning?
         }
 
-        for name in category.all_names() {
-            if self.by_name_or_alias.contains_key(&name.to_lowercase()) {
-                bail!(
+        // the aliases of archived categories are not in use anymore
+        let keys = if category.archived {
+            vec![category.name.as_str()]
+        } else {
+            category.all_names()
+        };
+
+        for name in keys {
+            let lc = name.to_lowercase();
+            if let Some(other) = self
+                .by_name_or_alias
+                .get(&lc)
+                .or_else(|| self.categories.get(&lc))
+            {
+                return Err(Error::conflict(format!(
                     "Duplicate category key: '{}' is used by '{}' and '{}'",
-                    name,
-                    category.name,
-                    self.find(name).unwrap().name
-                );
+                    name, category.name, other.name
+                )));
             }
         }
 
@@ -41,16 +60,21 @@ impl CategoryLookup {
 
         self.categories
             .insert(String::from(&cat_rc.name).to_lowercase(), cat_rc.clone());
+        self.in_order.push(cat_rc.clone());
 
-        for name in cat_rc.all_names() {
-            self.by_name_or_alias
-                .insert(name.to_string().to_lowercase(), cat_rc.clone());
+        // archived categories are only reachable by their name
+        if !cat_rc.archived {
+            for name in cat_rc.all_names() {
+                self.by_name_or_alias
+                    .insert(name.to_string().to_lowercase(), cat_rc.clone());
+            }
         }
 
         Ok(())
     }
 
-    /// Find a category by its name of alias
+    /// Find an active (not archived) category by its name of alias
+    #[allow(clippy::manual_map)]
     pub fn find<S: AsRef<str>>(&self, alias_or_name: S) -> Option<Rc<Category>> {
         let lc = alias_or_name.as_ref().to_lowercase();
         match self.by_name_or_alias.get(&lc) {
@@ -59,13 +83,122 @@ impl CategoryLookup {
         }
     }
 
+    /// Get a category (including archived ones) by its name, e.g. for evaluating recorded
+    /// activities
+    pub fn get<S: AsRef<str>>(&self, name: S) -> Option<Rc<Category>> {
+        let lc = name.as_ref().to_lowercase();
+        self.categories.get(&lc).cloned()
+    }
+
+    /// Find a category by its name or alias, using the given matching mode.
+    /// If there is no match, the returned error contains a suggestion of the closest matching
+    /// name or alias (if any). An ambiguous prefix results in an error listing all candidates.
+    pub fn resolve<S: AsRef<str>>(
+        &self,
+        alias_or_name: S,
+        matching: CategoryMatching,
+    ) -> Result<Rc<Category>, Error> {
+        let alias_or_name = alias_or_name.as_ref();
+
+        if let Some(cat) = self.find(alias_or_name) {
+            return Ok(cat);
+        }
+
+        if let Some(cat) = self.get(alias_or_name) {
+            return Err(Error::ArchivedCategory {
+                name: cat.name.to_string(),
+            });
+        }
+
+        if matching == CategoryMatching::Prefix {
+            let mut candidates = self.find_by_prefix(alias_or_name);
+            match candidates.len() {
+                0 => {}
+                1 => return Ok(candidates.remove(0)),
+                _ => {
+                    return Err(Error::AmbiguousCategory {
+                        name: alias_or_name.to_string(),
+                        candidates: candidates.iter().map(|cat| cat.name.to_string()).collect(),
+                    })
+                }
+            }
+        }
+
+        Err(Error::UnknownCategory {
+            name: alias_or_name.to_string(),
+            suggestion: self.suggest(alias_or_name),
+        })
+    }
+
+    /// Find all categories with a name or alias starting with the given prefix (case
+    /// insensitive), sorted by name
+    pub fn find_by_prefix<S: AsRef<str>>(&self, prefix: S) -> Vec<Rc<Category>> {
+        let prefix = prefix.as_ref().to_lowercase();
+
+        let mut result: Vec<Rc<Category>> = self
+            .categories
+            .values()
+            .filter(|cat| !cat.archived)
+            .filter(|cat| {
+                cat.all_names()
+                    .iter()
+                    .any(|name| name.to_lowercase().starts_with(&prefix))
+            })
+            .cloned()
+            .collect();
+        result.sort_by(|a, b| a.name.cmp(&b.name));
+
+        result
+    }
+
+    /// Find the known name or alias most similar to the given (unknown) one.
+    /// Returns None if nothing is similar enough to be a plausible typo.
+    pub fn suggest<S: AsRef<str>>(&self, alias_or_name: S) -> Option<String> {
+        let lc = alias_or_name.as_ref().to_lowercase();
+
+        self.categories
+            .values()
+            .filter(|cat| !cat.archived)
+            .flat_map(|cat| cat.all_names())
+            .map(|name| (name, strsim::jaro_winkler(&lc, &name.to_lowercase())))
+            .filter(|(_, similarity)| *similarity >= SUGGESTION_THRESHOLD)
+            .max_by(|(name_a, a), (name_b, b)| {
+                a.partial_cmp(b).unwrap().then_with(|| name_b.cmp(name_a))
+            })
+            .map(|(name, _)| name.to_string())
+    }
+
     #[cfg(test)]
     pub fn len(&self) -> usize {
         self.categories.len()
     }
 
-    pub fn iter(&self) -> std::collections::hash_map::Values<'_, String, Rc<Category>> {
-        self.categories.values()
+    /// Iterate over all categories (including archived ones) in the order they were added
+    pub fn iter(&self) -> std::slice::Iter<'_, Rc<Category>> {
+        self.in_order.iter()
+    }
+
+    /// The daily targets on the given day: The goals of all active categories, unless the plan
+    /// covers that category on that day - even with a rest day
+    pub fn targets_on(&self, day: NaiveDate, plan: Option<&Plan>) -> BTreeMap<String, u32> {
+        let mut targets: BTreeMap<String, u32> = self
+            .iter()
+            .filter(|category| !category.archived)
+            .filter_map(|category| Some((category.name.to_string(), category.goal?)))
+            .collect();
+
+        let index = plan.and_then(|plan| u32::try_from((day - plan.start).num_days()).ok());
+        if let (Some(plan), Some(index)) = (plan, index) {
+            for schedule in &plan.schedules {
+                match schedule.schedule.target(index) {
+                    Some(0) => targets.remove(&schedule.category),
+                    Some(target) => targets.insert(schedule.category.to_string(), target),
+                    None => None,
+                };
+            }
+        }
+
+        targets
     }
 }
 
@@ -98,6 +231,107 @@ mod tests {
         assert_eq!("Pushups", &(lookup.find("push").unwrap().name));
     }
 
+    #[test]
+    fn suggestions() {
+        let mut lookup = CategoryLookup::new();
+
+        lookup
+            .add(Category::new("Pushups", 1.0, vec!["pu", "push"]))
+            .unwrap();
+        lookup
+            .add(Category::new("Burpees", 1.5, vec!["bu"]))
+            .unwrap();
+
+        assert_eq!(Some("Pushups".to_string()), lookup.suggest("pushpus"));
+        assert_eq!(Some("Burpees".to_string()), lookup.suggest("BURPESS"));
+        assert_eq!(None, lookup.suggest("Lunges"));
+
+        assert_eq!(
+            "Pushups",
+            lookup.resolve("PU", CategoryMatching::Exact).unwrap().name
+        );
+        assert_eq!(
+            "Activity category 'burpes' is not known. Did you mean 'Burpees'?",
+            lookup
+                .resolve("burpes", CategoryMatching::Exact)
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "Activity category 'Lunges' is not known",
+            lookup
+                .resolve("Lunges", CategoryMatching::Prefix)
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn prefix_matching() {
+        let mut lookup = CategoryLookup::new();
+
+        lookup
+            .add(Category::new("Pushups", 1.0, vec!["pu"]))
+            .unwrap();
+        lookup
+            .add(Category::new("Pullups", 1.0, vec!["pl"]))
+            .unwrap();
+        lookup
+            .add(Category::new("Burpees", 1.5, vec!["bu"]))
+            .unwrap();
+
+        let prefix = CategoryMatching::Prefix;
+        assert_eq!("Pushups", lookup.resolve("push", prefix).unwrap().name);
+        assert_eq!("Burpees", lookup.resolve("BUR", prefix).unwrap().name);
+        assert_eq!("Pushups", lookup.resolve("pu", prefix).unwrap().name);
+        assert_eq!(
+            "Activity category 'p' is ambiguous, it could be any of: Pullups, Pushups",
+            lookup.resolve("p", prefix).unwrap_err().to_string()
+        );
+
+        lookup
+            .resolve("push", CategoryMatching::Exact)
+            .expect_err("Exact matching should not resolve prefixes");
+    }
+
+    #[test]
+    fn archived() {
+        let mut lookup = CategoryLookup::new();
+
+        lookup
+            .add(Category::new("Pushups", 1.0, vec!["pu"]))
+            .unwrap();
+        let mut burpees = Category::new("Burpees", 1.5, vec!["bu"]);
+        burpees.archived = true;
+        lookup.add(burpees).unwrap();
+
+        assert!(lookup.find("Burpees").is_none());
+        assert!(lookup.find("bu").is_none());
+        assert_eq!(1.5, lookup.get("burpees").unwrap().weight);
+        assert!(lookup.get("bu").is_none());
+        assert!(lookup.find_by_prefix("b").is_empty());
+        assert_eq!(None, lookup.suggest("burpes"));
+        assert_eq!(
+            "Activity category 'Burpees' is archived and can't be used anymore",
+            lookup
+                .resolve("burpees", CategoryMatching::Prefix)
+                .unwrap_err()
+                .to_string()
+        );
+
+        // the alias of an archived category is free to use, its name is not
+        lookup
+            .add(Category::new("Bunnyhops", 1.0, vec!["bu"]))
+            .unwrap();
+        lookup
+            .add(Category::new("Burpees", 1.0, vec!["bp"]))
+            .unwrap();
+        assert_eq!(1.5, lookup.get("Burpees").unwrap().weight);
+        lookup
+            .add(Category::new("Pullups", 1.0, vec!["burpees"]))
+            .expect_err("Should return an error for alias colliding with archived name");
+    }
+
     #[test]
     fn duplicates() {
         let mut lookup = CategoryLookup::new();
@@ -0,0 +1,236 @@
+use crate::error::Error;
+use crate::model::Config;
+use crate::storage::{self, Storage};
+use chrono::{DateTime, Local};
+use std::fmt;
+
+/// Progress of the current day towards the daily goal of a category
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoalProgress {
+    pub category: String,
+    /// Repetitions to reach on the whole day
+    pub goal: u32,
+    /// Repetitions done so far
+    pub reps: u32,
+    /// Repetitions that should be done by now according to the pace model
+    pub expected: u32,
+}
+
+impl GoalProgress {
+    pub fn is_behind(&self) -> bool {
+        self.reps < self.expected
+    }
+
+    pub fn is_reached(&self) -> bool {
+        self.reps >= self.goal
+    }
+}
+
+impl fmt::Display for GoalProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} of {} reps", self.category, self.reps, self.goal)?;
+        if self.is_reached() {
+            write!(f, " - goal reached")
+        } else if self.is_behind() {
+            write!(
+                f,
+                " - {} should be done by now, {} to catch up",
+                self.expected,
+                self.expected - self.reps
+            )
+        } else {
+            write!(f, " - on track")
+        }
+    }
+}
+
+/// Compare the progress of the day containing `now` against the goals of all active categories,
+/// or the targets of the training plan where it has one
+///
+/// # Returns
+/// One entry per category with a target, in the order the categories were defined
+pub fn progress(
+    storage: &dyn Storage,
+    config: &Config,
+    now: &DateTime<Local>,
+) -> Result<Vec<GoalProgress>, Error> {
+    let day = config.day_of(now);
+    let percent = config
+        .pace
+        .expected_percent(config.time_of_day(now), config.day_start());
+
+    let activities: Vec<_> = storage
+        .read_days(&day, &day)?
+        .into_iter()
+        .filter(|activity| activity.timestamp <= *now)
+        .collect();
+
+    let categories = storage.read_categories()?;
+    let plan = storage::read_plan(config)?;
+    let targets = categories.targets_on(day.naive_local(), plan.as_ref());
+    let mut result = Vec::new();
+    for category in categories.iter().filter(|c| !c.archived) {
+        if let Some(&goal) = targets.get(&category.name) {
+            let reps = activities
+                .iter()
+                .filter(|activity| activity.category == category.name)
+                .map(|activity| activity.reps)
+                .sum();
+            result.push(GoalProgress {
+                category: category.name.to_string(),
+                goal,
+                reps,
+                // percent is at most 100, so the result fits again
+                expected: (u64::from(goal) * u64::from(percent) / 100) as u32,
+            });
+        }
+    }
+
+    Ok(result)
+}
+
+//
+// Tests ---------------------------------------------------
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Activity, Category, Pace, Plan, PlanSchedule, Schedule};
+    use crate::storage::memory::MemoryStorage;
+    use chrono::{NaiveDate, NaiveTime, TimeZone};
+
+    #[test]
+    fn pace() {
+        let pace = Pace::default();
+        let midnight = NaiveTime::from_hms(0, 0, 0);
+        let percent = |h, m| pace.expected_percent(NaiveTime::from_hms(h, m, 0), midnight);
+
+        assert_eq!(0, percent(0, 0));
+        assert_eq!(15, percent(6, 0));
+        assert_eq!(30, percent(12, 0));
+        assert_eq!(52, percent(15, 0));
+        assert_eq!(75, percent(18, 0));
+        assert_eq!(100, percent(21, 0));
+        assert_eq!(100, percent(23, 59));
+
+        // with a day starting at 04:00, 02:00 is late in the evening
+        let four = NaiveTime::from_hms(4, 0, 0);
+        assert_eq!(
+            100,
+            pace.expected_percent(NaiveTime::from_hms(2, 0, 0), four)
+        );
+        assert_eq!(0, pace.expected_percent(four, four));
+
+        assert!("18:00=75,x".parse::<Pace>().is_err());
+        assert!("".parse::<Pace>().is_err());
+    }
+
+    #[test]
+    fn goal_progress() {
+        let mut pushups = Category::new("Pushups", 1.0, vec!["pu"]);
+        pushups.goal = Some(100);
+        let mut burpees = Category::new("Burpees", 1.5, vec!["bu"]);
+        burpees.goal = Some(40);
+        let mut archived = Category::new("Situps", 1.0, Vec::<String>::new());
+        archived.goal = Some(10);
+        archived.archived = true;
+        let storage = MemoryStorage::new(vec![
+            pushups,
+            burpees,
+            Category::new("Lunges", 1.0, Vec::<String>::new()),
+            archived,
+        ]);
+
+        let day = Local.ymd(2021, 3, 14);
+        store(&storage, day.pred().and_hms(20, 0, 0), 50, "Pushups");
+        store(&storage, day.and_hms(9, 0, 0), 40, "Pushups");
+        store(&storage, day.and_hms(10, 0, 0), 25, "Pushups");
+        store(&storage, day.and_hms(11, 0, 0), 40, "Burpees");
+        store(&storage, day.and_hms(19, 0, 0), 20, "Pushups");
+
+        let config = Config::new("/nonexistent");
+        let progress = progress(&storage, &config, &day.and_hms(18, 0, 0)).unwrap();
+        assert_eq!(
+            vec![
+                GoalProgress {
+                    category: "Pushups".to_string(),
+                    goal: 100,
+                    reps: 65,
+                    expected: 75,
+                },
+                GoalProgress {
+                    category: "Burpees".to_string(),
+                    goal: 40,
+                    reps: 40,
+                    expected: 30,
+                }
+            ],
+            progress
+        );
+        assert!(progress[0].is_behind());
+        assert!(!progress[1].is_behind());
+        assert_eq!(
+            "Pushups: 65 of 100 reps - 75 should be done by now, 10 to catch up",
+            progress[0].to_string()
+        );
+        assert_eq!(
+            "Burpees: 40 of 40 reps - goal reached",
+            progress[1].to_string()
+        );
+    }
+
+    #[test]
+    fn plan_targets() {
+        let mut pushups = Category::new("Pushups", 1.0, vec!["pu"]);
+        pushups.goal = Some(100);
+        let storage = MemoryStorage::new(vec![pushups, Category::new("Squats", 1.0, vec!["sq"])]);
+        let day = Local.ymd(2021, 3, 14);
+        store(&storage, day.and_hms(9, 0, 0), 15, "Squats");
+
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let config = Config::new(tmp_dir.path().to_str().unwrap());
+        let plan = Plan {
+            start: NaiveDate::from_ymd(2021, 3, 14),
+            schedules: vec![PlanSchedule {
+                category: "Squats".to_string(),
+                schedule: Schedule::Days(vec![20]),
+            }],
+        };
+        storage::write_plan(&config, Some(&plan)).unwrap();
+
+        let progress = progress(&storage, &config, &day.and_hms(21, 0, 0)).unwrap();
+        assert_eq!(
+            vec![("Pushups", 100, 0), ("Squats", 20, 15)],
+            progress
+                .iter()
+                .map(|p| (p.category.as_str(), p.goal, p.reps))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn huge_goal() {
+        let mut steps = Category::new("Steps", 0.01, vec!["st"]);
+        steps.goal = Some(u32::MAX);
+        let storage = MemoryStorage::new(vec![steps]);
+
+        let config = Config::new("/nonexistent");
+        let now = Local.ymd(2021, 3, 14).and_hms(18, 0, 0);
+        let progress = progress(&storage, &config, &now).unwrap();
+        assert_eq!(
+            (u64::from(u32::MAX) * 75 / 100) as u32,
+            progress[0].expected
+        );
+    }
+
+    fn store(storage: &MemoryStorage, timestamp: DateTime<Local>, reps: u32, category: &str) {
+        storage
+            .store(&Activity {
+                timestamp,
+                reps,
+                category: category.to_string(),
+            })
+            .unwrap();
+    }
+}
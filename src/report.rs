@@ -1,21 +1,146 @@
 mod common;
+mod plan;
 mod sliding;
+mod team;
 mod today;
 
 #[cfg(test)]
 mod test_common;
 
-use crate::model::Config;
-use chrono::Local;
+use crate::error::Error;
+use crate::model::{CategoryLookup, Config, Plan};
+use crate::storage::Storage;
+use chrono::{Date, Duration, Local};
+use std::collections::BTreeMap;
 
-pub fn today(config: &Config) -> Result<(), String> {
-    today::run(config)
+pub use plan::{Adherence, PlanDay, PlanEntry, PlanReport};
+pub use team::Member;
+
+//
+// Report data ------------------------------
+//
+
+/// All sets logged on a single day
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayReport {
+    pub day: Date<Local>,
+    /// The repetitions of each set, by category name
+    pub sets_by_category: BTreeMap<String, Vec<u32>>,
+    /// Total over all categories, using the weights valid on that day
+    pub weighted_total: u32,
+    /// The repetitions to reach per category: From the training plan if it covers the category on
+    /// that day, otherwise the category's daily goal
+    pub targets: BTreeMap<String, u32>,
+}
+
+impl DayReport {
+    /// The total repetitions logged for the given category (by its name)
+    pub fn reps(&self, category: &str) -> u32 {
+        self.sets_by_category
+            .get(category)
+            .map(|sets| sets.iter().sum())
+            .unwrap_or(0)
+    }
+}
+
+/// Daily totals over a range of days
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeReport {
+    /// Name of the category the report focuses on, if any
+    pub category: Option<String>,
+    /// One entry per day, including days without activities
+    pub days: Vec<DayTotals>,
+}
+
+/// Aggregated repetitions of a single day
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayTotals {
+    pub day: Date<Local>,
+    pub reps_by_category: BTreeMap<String, u32>,
+    /// Total over all categories, using the weights valid on that day
+    pub weighted_total: u32,
+}
+
+impl DayTotals {
+    /// The total repetitions logged for the given category (by its name)
+    pub fn reps(&self, category: &str) -> u32 {
+        *self.reps_by_category.get(category).unwrap_or(&0)
+    }
+}
+
+//
+// Report generation ------------------------
+//
+
+/// Collect the data for the report on a single day, with the targets of the training plan if given
+pub fn day(
+    day: &Date<Local>,
+    storage: &dyn Storage,
+    plan: Option<&Plan>,
+    config: &Config,
+) -> Result<DayReport, Error> {
+    today::build(day, storage, plan, config)
+}
+
+/// Collect the daily totals for the given range of days (both ends inclusive), optionally focusing
+/// on a single category
+pub fn range(
+    start: &Date<Local>,
+    end: &Date<Local>,
+    category: Option<&str>,
+    storage: &dyn Storage,
+    config: &Config,
+) -> Result<RangeReport, Error> {
+    sliding::build(start, end, category, storage, config)
+}
+
+/// Collect the adherence to the training plan up to the given day
+pub fn plan_adherence(
+    plan: &Plan,
+    today: &Date<Local>,
+    storage: &dyn Storage,
+    config: &Config,
+) -> Result<PlanReport, Error> {
+    plan::build(plan, today.naive_local(), storage, config)
+}
+
+//
+// Printed reports --------------------------
+//
+
+pub fn today(storage: &dyn Storage, config: &Config) -> Result<(), Error> {
+    today::run(storage, config)
+}
+
+/// Print the daily totals for the given number of days up to today
+pub fn sliding(
+    number_of_days: u32,
+    category: Option<String>,
+    storage: &dyn Storage,
+    config: &Config,
+) -> Result<(), Error> {
+    sliding::sliding_days(config.today(), number_of_days, category, storage, config)
 }
 
-pub fn sliding_month(category: Option<String>, config: &Config) -> Result<(), String> {
-    sliding::sliding_days(Local::now().date(), 31, category, config)
+/// Print the leaderboard of the given team members for the given number of days up to today
+pub fn team(
+    number_of_days: u32,
+    members: &[Member],
+    shared_categories: Option<&CategoryLookup>,
+    config: &Config,
+) -> Result<(), Error> {
+    let end = config.today();
+    let start = end - Duration::days((number_of_days - 1).into());
+    let stats = team::build(members, shared_categories, &start, &end)?;
+
+    print!("{}", team::format(&stats, &start, &end, config));
+    Ok(())
 }
 
-pub fn sliding_week(category: Option<String>, config: &Config) -> Result<(), String> {
-    sliding::sliding_days(Local::now().date(), 7, category, config)
+/// Print the adherence to the training plan up to today
+pub fn plan(plan: &Plan, storage: &dyn Storage, config: &Config) -> Result<(), Error> {
+    let report = plan::build(plan, config.today().naive_local(), storage, config)?;
+
+    print!("{}", plan::format(&report, config));
+    Ok(())
 }
@@ -1,15 +1,21 @@
-use crate::model::{Activity, CategoryLookup};
+use crate::model::{Activity, CategoryLookup, Config};
 use chrono::{Date, Local};
 use std::collections::HashMap;
 
-/// Calculate the weighted total repetitions over the the given activities
-pub fn weighted_total(activities: &[Activity], categories: &CategoryLookup) -> u32 {
+/// Calculate the weighted total repetitions over the the given activities, using the weights valid
+/// on the days they count for
+pub fn weighted_total(
+    activities: &[Activity],
+    categories: &CategoryLookup,
+    config: &Config,
+) -> u32 {
     let mut total = 0;
 
     for activity in activities {
         let cat = &activity.category;
-        if let Some(category) = categories.find(cat) {
-            total += ((activity.reps as f64) * category.weight) as u32;
+        if let Some(category) = categories.get(cat) {
+            let weight = category.weight_on(&config.day_of(&activity.timestamp));
+            total += ((activity.reps as f64) * weight) as u32;
         } else {
             // default to a weight of 1 (e.g. for categories that don't exist anymore)
             total += activity.reps;
@@ -34,13 +40,14 @@ impl DayStats {
         }
     }
 
-    /// Calculate the weighted total of all repetitions in this day
+    /// Calculate the weighted total of all repetitions in this day, using the weights valid on
+    /// that day
     pub fn reps_total(&self, categories: &CategoryLookup) -> u32 {
         let mut total = 0;
 
         for (cat, reps) in self.reps_by_category.iter() {
-            if let Some(category) = categories.find(cat) {
-                total += ((*reps as f64) * category.weight) as u32;
+            if let Some(category) = categories.get(cat) {
+                total += ((*reps as f64) * category.weight_on(&self.day)) as u32;
             } else {
                 // default to a weight of 1 (e.g. for categories that don't exist anymore)
                 total += reps;
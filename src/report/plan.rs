@@ -0,0 +1,297 @@
+use crate::error::Error;
+use crate::model::{Config, Plan};
+use crate::storage::Storage;
+use chrono::{Datelike, Local, NaiveDate, TimeZone};
+use itertools::Itertools;
+use std::collections::HashMap;
+
+/// Adherence to a training plan up to a given day
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanReport {
+    pub plan: Plan,
+    /// The day the report was made for
+    pub today: NaiveDate,
+    /// All days of the plan up to today
+    pub days: Vec<PlanDay>,
+}
+
+/// The targets of a single day of a training plan, and the repetitions done
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanDay {
+    pub day: NaiveDate,
+    /// One entry per category with a target on that day, empty on rest days
+    pub entries: Vec<PlanEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanEntry {
+    pub category: String,
+    pub reps: u32,
+    pub target: u32,
+}
+
+impl PlanEntry {
+    pub fn reached(&self) -> bool {
+        self.reps >= self.target
+    }
+}
+
+/// How often the target of one category was reached
+#[derive(Debug, Clone, PartialEq)]
+pub struct Adherence {
+    pub category: String,
+    pub days_reached: u32,
+    /// Days with a target so far. Today only counts once its target is reached.
+    pub days_due: u32,
+}
+
+impl PlanReport {
+    /// The adherence per category, in the order of the plan
+    pub fn adherence(&self) -> Vec<Adherence> {
+        self.plan
+            .schedules
+            .iter()
+            .map(|schedule| {
+                let entries = self
+                    .days
+                    .iter()
+                    .flat_map(|day| {
+                        let is_today = day.day == self.today;
+                        day.entries
+                            .iter()
+                            .filter(move |entry| !is_today || entry.reached())
+                    })
+                    .filter(|entry| entry.category == schedule.category)
+                    .collect::<Vec<_>>();
+
+                Adherence {
+                    category: schedule.category.to_string(),
+                    days_reached: entries.iter().filter(|entry| entry.reached()).count() as u32,
+                    days_due: entries.len() as u32,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Collect the targets and repetitions of all days of the plan up to the given day
+pub fn build(
+    plan: &Plan,
+    today: NaiveDate,
+    storage: &dyn Storage,
+    config: &Config,
+) -> Result<PlanReport, Error> {
+    let last = today.min(plan.end());
+
+    let mut reps: HashMap<(NaiveDate, String), u32> = HashMap::new();
+    if last >= plan.start {
+        let local = |date: NaiveDate| Local.from_local_date(&date).unwrap();
+        for activity in storage.read_days(&local(plan.start), &local(last))? {
+            let day = config.day_of(&activity.timestamp).naive_local();
+            *reps.entry((day, activity.category)).or_insert(0) += activity.reps;
+        }
+    }
+
+    let mut days = Vec::new();
+    let mut day = plan.start;
+    while day <= last {
+        let entries = plan
+            .targets_on(day)
+            .into_iter()
+            .map(|(category, target)| PlanEntry {
+                reps: reps.get(&(day, category.to_string())).copied().unwrap_or(0),
+                category,
+                target,
+            })
+            .collect();
+        days.push(PlanDay { day, entries });
+        day = day.succ();
+    }
+
+    Ok(PlanReport {
+        plan: plan.clone(),
+        today,
+        days,
+    })
+}
+
+/// Format the adherence report as string
+pub fn format(report: &PlanReport, config: &Config) -> String {
+    let plan = &report.plan;
+    let mut result = format!(
+        "Training plan from {} to {}",
+        plan.start.format(&config.date_format),
+        plan.end().format(&config.date_format)
+    );
+
+    if report.today < plan.start {
+        result.push_str(&format!(
+            ", starts in {} days\n",
+            (plan.start - report.today).num_days()
+        ));
+        return result;
+    } else if report.today > plan.end() {
+        result.push_str(", finished\n\n");
+    } else {
+        result.push_str(&format!(
+            ", day {} of {}\n\n",
+            (report.today - plan.start).num_days() + 1,
+            plan.days()
+        ));
+    }
+
+    for day in &report.days {
+        let entries = if day.entries.is_empty() {
+            "rest day".to_string()
+        } else {
+            day.entries
+                .iter()
+                .map(|entry| {
+                    let verdict = if entry.reached() {
+                        ""
+                    } else if day.day == report.today {
+                        " open"
+                    } else {
+                        " missed"
+                    };
+                    format!(
+                        "{} {}/{}{}",
+                        entry.category, entry.reps, entry.target, verdict
+                    )
+                })
+                .join(", ")
+        };
+        result.push_str(&format!(
+            "  {:3} {}: {}\n",
+            day.day.weekday(),
+            day.day.format(&config.date_format),
+            entries
+        ));
+    }
+
+    result.push('\n');
+    for adherence in report.adherence() {
+        result.push_str(&format!(
+            "{}: {} of {} days reached ({}%)\n",
+            adherence.category,
+            adherence.days_reached,
+            adherence.days_due,
+            (adherence.days_reached * 100)
+                .checked_div(adherence.days_due)
+                .unwrap_or(100)
+        ));
+    }
+
+    result
+}
+
+//
+// Tests ---------------------------------------------------
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Activity, Category, Increase, PlanSchedule, Schedule};
+    use crate::storage::memory::MemoryStorage;
+
+    #[test]
+    fn adherence() {
+        let storage = MemoryStorage::new(vec![
+            Category::new("Pushups", 1.0, vec!["pu"]),
+            Category::new("Squats", 1.0, vec!["sq"]),
+        ]);
+        for (day_of_month, reps, category) in &[
+            (1, 20, "Pushups"),
+            (1, 30, "Squats"),
+            (2, 5, "Pushups"),
+            (2, 20, "Squats"),
+            (3, 25, "Pushups"),
+            (8, 21, "Pushups"),
+            (9, 50, "Pushups"),
+        ] {
+            storage
+                .store(&Activity {
+                    timestamp: Local.ymd(2021, 3, *day_of_month).and_hms(12, 0, 0),
+                    reps: *reps,
+                    category: category.to_string(),
+                })
+                .unwrap();
+        }
+
+        let plan = Plan {
+            start: NaiveDate::from_ymd(2021, 3, 1),
+            schedules: vec![
+                PlanSchedule {
+                    category: "Pushups".to_string(),
+                    schedule: Schedule::Weekly {
+                        initial: 20,
+                        increase: Increase::Reps(2),
+                        weeks: 2,
+                    },
+                },
+                PlanSchedule {
+                    category: "Squats".to_string(),
+                    schedule: Schedule::Days(vec![30, 0, 30]),
+                },
+            ],
+        };
+        let config = Config::new("/nonexistent");
+
+        let report = build(&plan, NaiveDate::from_ymd(2021, 3, 8), &storage, &config).unwrap();
+        assert_eq!(8, report.days.len());
+        assert_eq!(
+            vec![
+                Adherence {
+                    category: "Pushups".to_string(),
+                    days_reached: 2,
+                    days_due: 7,
+                },
+                Adherence {
+                    category: "Squats".to_string(),
+                    days_reached: 1,
+                    days_due: 2,
+                }
+            ],
+            report.adherence()
+        );
+        assert_eq!(
+            "\
+Training plan from 2021-03-01 to 2021-03-14, day 8 of 14
+
+  Mon 2021-03-01: Pushups 20/20, Squats 30/30
+  Tue 2021-03-02: Pushups 5/20 missed
+  Wed 2021-03-03: Pushups 25/20, Squats 0/30 missed
+  Thu 2021-03-04: Pushups 0/20 missed
+  Fri 2021-03-05: Pushups 0/20 missed
+  Sat 2021-03-06: Pushups 0/20 missed
+  Sun 2021-03-07: Pushups 0/20 missed
+  Mon 2021-03-08: Pushups 21/22 open
+
+Pushups: 2 of 7 days reached (28%)
+Squats: 1 of 2 days reached (50%)
+",
+            format(&report, &config)
+        );
+
+        // today counts once its target is reached, days after the plan don't count at all
+        let report = build(&plan, NaiveDate::from_ymd(2021, 3, 9), &storage, &config).unwrap();
+        assert_eq!((3, 9), reached(&report));
+        let report = build(&plan, NaiveDate::from_ymd(2021, 4, 1), &storage, &config).unwrap();
+        assert_eq!(14, report.days.len());
+        assert!(format(&report, &config)
+            .starts_with("Training plan from 2021-03-01 to 2021-03-14, finished\n"));
+
+        let report = build(&plan, NaiveDate::from_ymd(2021, 2, 26), &storage, &config).unwrap();
+        assert!(report.days.is_empty());
+        assert_eq!(
+            "Training plan from 2021-03-01 to 2021-03-14, starts in 3 days\n",
+            format(&report, &config)
+        );
+    }
+
+    fn reached(report: &PlanReport) -> (u32, u32) {
+        let pushups = &report.adherence()[0];
+        (pushups.days_reached, pushups.days_due)
+    }
+}
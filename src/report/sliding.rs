@@ -1,25 +1,106 @@
-use crate::model::{Activity, CategoryLookup, Config};
+use crate::error::Error;
+use crate::model::{Activity, Config};
 use crate::report::common::DayStats;
-use crate::storage;
+use crate::report::{DayTotals, RangeReport};
+use crate::storage::Storage;
 use chrono::{Date, Datelike, Duration, Local};
 use std::collections::HashMap;
 use std::vec::Vec;
 
+/// Print the report for the given number of days up to `end_date`
 pub fn sliding_days(
     end_date: Date<Local>,
     number_of_days: u32,
     category: Option<String>,
+    storage: &dyn Storage,
     config: &Config,
-) -> Result<(), String> {
+) -> Result<(), Error> {
     let start_date = end_date - Duration::days((number_of_days - 1).into());
+    let report = build(&start_date, &end_date, category.as_deref(), storage, config)?;
 
-    let categories = storage::read_categories(config)?;
-    let activities = storage::read_days(&start_date, &end_date, config)?;
-    let stats = build_stats(&activities, &start_date, &end_date);
+    print!("{}", format(&report, config));
+    Ok(())
+}
+
+/// Collect the data for the report on the given range of days (both ends inclusive), either on a
+/// single category or on the weighted totals
+pub fn build(
+    start_date: &Date<Local>,
+    end_date: &Date<Local>,
+    category: Option<&str>,
+    storage: &dyn Storage,
+    config: &Config,
+) -> Result<RangeReport, Error> {
+    let categories = storage.read_categories()?;
+    let category = match category {
+        // archived categories can still be reported on by their full name
+        Some(name) => match categories.get(name) {
+            Some(cat) => Some(cat.name.to_string()),
+            None => Some(
+                categories
+                    .resolve(name, config.category_matching)?
+                    .name
+                    .to_string(),
+            ),
+        },
+        None => None,
+    };
 
-    print_stats(&stats, category, &categories);
+    let activities = storage.read_days(start_date, end_date)?;
+    let days = build_stats(&activities, start_date, end_date, config)
+        .into_iter()
+        .map(|stats| DayTotals {
+            day: stats.day,
+            weighted_total: stats.reps_total(&categories),
+            reps_by_category: stats.reps_by_category.into_iter().collect(),
+        })
+        .collect();
 
-    Ok(())
+    Ok(RangeReport { category, days })
+}
+
+/// Format the given report on a range of days as string. Weeks are separated by a line.
+pub fn format(report: &RangeReport, config: &Config) -> String {
+    let mut result = String::new();
+
+    let title = match &report.category {
+        Some(cat_name) => format!(
+            "Report on {} for the past {} days\n\n",
+            cat_name,
+            report.days.len()
+        ),
+        None => format!(
+            "Report on the weighted total for the past {} days\n\n",
+            report.days.len()
+        ),
+    };
+    result.push_str(&title);
+
+    for (idx, day) in report.days.iter().enumerate() {
+        let label = format!(
+            "{:3} {}",
+            day.day.weekday(),
+            day.day.format(&config.date_format)
+        );
+        let line = match &report.category {
+            Some(cat_name) => format!(
+                "{}: {:>5} reps ({:>5} total)",
+                label,
+                day.reps(cat_name),
+                day.weighted_total
+            ),
+            None => format!("{}: {:>5} total", label, day.weighted_total),
+        };
+
+        if idx > 0 && day.day.weekday() == config.week_starts_on {
+            result.push_str(&"-".repeat(line.chars().count()));
+            result.push('\n');
+        }
+        result.push_str(&line);
+        result.push('\n');
+    }
+
+    result
 }
 
 //
@@ -32,15 +113,22 @@ pub fn sliding_days(
 ///  * `activities`: All recorded activities in the given interval
 ///  * `start`: Interval start date
 ///  * `end`: Interval end date
+///  * `config`: Determines the day each activity counts for
 ///
 ///  # Returns
 ///  A vector with one entry per day in (start..=end), each holding the total number of reps for
 ///  all categories
-fn build_stats(activities: &[Activity], start: &Date<Local>, end: &Date<Local>) -> Vec<DayStats> {
+#[allow(clippy::needless_borrow)]
+fn build_stats(
+    activities: &[Activity],
+    start: &Date<Local>,
+    end: &Date<Local>,
+    config: &Config,
+) -> Vec<DayStats> {
     let mut by_day: HashMap<Date<Local>, DayStats> = HashMap::new();
 
     for activity in activities {
-        let today = activity.timestamp.date();
+        let today = config.day_of(&activity.timestamp);
 
         let stats = by_day.entry(today).or_insert_with(|| DayStats::new(&today));
         stats.add(&activity);
@@ -57,41 +145,6 @@ fn build_stats(activities: &[Activity], start: &Date<Local>, end: &Date<Local>)
     results
 }
 
-fn print_stats(stats: &[DayStats], category: Option<String>, categories: &CategoryLookup) {
-    match category {
-        Some(ref cat) => {
-            let cat_name = &categories.find(cat).unwrap().name;
-            println!(
-                "Report on {} for the past {} days\n", cat_name, stats.len()
-            );
-
-            for day in stats {
-                println!(
-                    "{:3}: {:>5} reps ({:>5} total)",
-                    day.day.weekday(),
-                    day.reps_by_category.get(cat_name).unwrap_or(&0),
-                    day.reps_total(categories)
-                );
-            }
-        }
-
-        None => {
-            println!(
-                "Report on the weighted total for the past {} days\n",
-                stats.len()
-            );
-
-            for day in stats {
-                println!(
-                    "{:3}: {:>5} total",
-                    day.day.weekday(),
-                    day.reps_total(categories)
-                );
-            }
-        }
-    }
-}
-
 //
 // Tests ---------------------------------------------------
 //
@@ -99,10 +152,12 @@ fn print_stats(stats: &[DayStats], category: Option<String>, categories: &Catego
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::model::Activity;
-    use chrono::{Local, TimeZone};
+    use crate::model::{Activity, Category};
+    use crate::storage::memory::MemoryStorage;
+    use chrono::{Local, TimeZone, Utc, Weekday};
 
     #[test]
+    #[allow(clippy::vec_init_then_push)]
     fn build_stats_basic() {
         let mut activities = Vec::new();
 
@@ -117,7 +172,7 @@ mod tests {
 
         let start = Local.ymd(2020, 7, 1);
         let end = Local.ymd(2020, 7, 30);
-        let stats = build_stats(&activities, &start, &end);
+        let stats = build_stats(&activities, &start, &end, &Config::new("/nonexistent"));
 
         assert_eq!(30, stats.len());
 
@@ -145,6 +200,109 @@ mod tests {
         }
     }
 
+    #[test]
+    fn build_stats_home_timezone() {
+        // 23:00 UTC is already the next day in New Zealand
+        let timestamp = Utc.ymd(2020, 7, 5).and_hms(23, 0, 0).with_timezone(&Local);
+        let activities = vec![Activity {
+            timestamp,
+            reps: 10,
+            category: "Pushups".to_string(),
+        }];
+        let start = Local.ymd(2020, 7, 5);
+        let end = Local.ymd(2020, 7, 6);
+
+        let mut config = Config::new("/nonexistent");
+        config.home_timezone = Some(chrono_tz::Pacific::Auckland);
+        let stats = build_stats(&activities, &start, &end, &config);
+        assert_eq!(vec![0, 10], days_reps(&stats));
+
+        config.home_timezone = Some(chrono_tz::America::Los_Angeles);
+        let stats = build_stats(&activities, &start, &end, &config);
+        assert_eq!(vec![10, 0], days_reps(&stats));
+    }
+
+    #[test]
+    fn report_from_storage() {
+        let storage = MemoryStorage::new(vec![
+            Category::new("Pushups", 1.0, vec!["pu"]),
+            Category::new("Burpees", 1.5, vec!["bu"]),
+        ]);
+        storage.store(&activity(5, 10, "Pushups")).unwrap();
+        storage.store(&activity(6, 20, "Burpees")).unwrap();
+        storage.store(&activity(6, 15, "Pushups")).unwrap();
+
+        let config = Config::new("/nonexistent");
+        let end = Local.ymd(2020, 7, 7);
+
+        assert_eq!(
+            "\
+Report on Pushups for the past 3 days
+
+Sun 2020-07-05:    10 reps (   10 total)
+----------------------------------------
+Mon 2020-07-06:    15 reps (   45 total)
+Tue 2020-07-07:     0 reps (    0 total)
+",
+            report(end, 3, Some("push"), &storage, &config)
+        );
+
+        assert_eq!(
+            "\
+Report on the weighted total for the past 2 days
+
+Mon 2020-07-06:    45 total
+Tue 2020-07-07:     0 total
+",
+            report(end, 2, None, &storage, &config)
+        );
+
+        let config = Config {
+            week_starts_on: Weekday::Sun,
+            date_format: "%d.%m.".to_string(),
+            ..config
+        };
+        assert_eq!(
+            "\
+Report on the weighted total for the past 3 days
+
+Sat 04.07.:     0 total
+-----------------------
+Sun 05.07.:    10 total
+Mon 06.07.:    45 total
+",
+            report(end.pred(), 3, None, &storage, &config)
+        );
+
+        assert_eq!(
+            "Activity category 'pushpus' is not known. Did you mean 'Pushups'?",
+            build(&end, &end, Some("pushpus"), &storage, &config)
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    fn report(
+        end: Date<Local>,
+        days: i64,
+        category: Option<&str>,
+        storage: &dyn Storage,
+        config: &Config,
+    ) -> String {
+        let start = end - Duration::days(days - 1);
+        format(
+            &build(&start, &end, category, storage, config).unwrap(),
+            config,
+        )
+    }
+
+    fn days_reps(stats: &[DayStats]) -> Vec<u32> {
+        stats
+            .iter()
+            .map(|s| s.reps_by_category.values().sum())
+            .collect()
+    }
+
     fn activity(day_of_month: u32, reps: u32, category: &str) -> Activity {
         let time = Local.ymd(2020, 7, day_of_month).and_hms(13, 45, 34);
         Activity {
@@ -0,0 +1,270 @@
+use crate::error::Error;
+use crate::model::{CategoryLookup, Config};
+use crate::report::common::DayStats;
+use crate::storage::Storage;
+use chrono::{Date, Local};
+use std::collections::HashMap;
+
+/// The data of one team member
+pub struct Member<'a> {
+    pub name: String,
+    pub storage: &'a dyn Storage,
+    /// The member's own config, determining the days activities count for
+    pub config: &'a Config,
+}
+
+/// Statistics of one team member for the reported period
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemberStats {
+    pub name: String,
+    pub weighted_total: u32,
+    /// Consecutive days with activities up to the end of the period. The last day only counts
+    /// if there already are activities on it.
+    pub streak: u32,
+    /// Days on which the goal of a category was reached, and days on which it could have been,
+    /// summed up over all categories with a daily goal
+    pub goals_reached: u32,
+    pub goal_days: u32,
+}
+
+/// Collect the statistics of all members for the given range of days (both ends inclusive),
+/// ranked by weighted total. The weights are taken from the shared categories if given, otherwise
+/// from each member's own categories. Goals always come from the member's own categories.
+pub fn build(
+    members: &[Member],
+    shared_categories: Option<&CategoryLookup>,
+    start: &Date<Local>,
+    end: &Date<Local>,
+) -> Result<Vec<MemberStats>, Error> {
+    let mut result = Vec::new();
+
+    for member in members {
+        let own_categories = member.storage.read_categories()?;
+        let categories = shared_categories.unwrap_or(&own_categories);
+
+        let mut by_day: HashMap<Date<Local>, DayStats> = HashMap::new();
+        for activity in member.storage.read_all()? {
+            let day = member.config.day_of(&activity.timestamp);
+            if day <= *end {
+                by_day
+                    .entry(day)
+                    .or_insert_with(|| DayStats::new(&day))
+                    .add(&activity);
+            }
+        }
+
+        let mut stats = MemberStats {
+            name: member.name.to_string(),
+            weighted_total: 0,
+            streak: streak(&by_day, end),
+            goals_reached: 0,
+            goal_days: 0,
+        };
+
+        let mut day = *start;
+        while day <= *end {
+            let day_stats = by_day.remove(&day).unwrap_or_else(|| DayStats::new(&day));
+            stats.weighted_total += day_stats.reps_total(categories);
+
+            for category in own_categories.iter().filter(|c| !c.archived) {
+                if let Some(goal) = category.goal {
+                    stats.goal_days += 1;
+                    let reps = day_stats.reps_by_category.get(&category.name);
+                    if reps.copied().unwrap_or(0) >= goal {
+                        stats.goals_reached += 1;
+                    }
+                }
+            }
+            day = day.succ();
+        }
+
+        result.push(stats);
+    }
+
+    result.sort_by(|a, b| {
+        b.weighted_total
+            .cmp(&a.weighted_total)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    Ok(result)
+}
+
+/// Format the leaderboard as string
+pub fn format(
+    stats: &[MemberStats],
+    start: &Date<Local>,
+    end: &Date<Local>,
+    config: &Config,
+) -> String {
+    let days = (*end - *start).num_days() + 1;
+    let mut result = format!(
+        "Team report for the past {} days ({} - {})\n\n",
+        days,
+        start.format(&config.date_format),
+        end.format(&config.date_format)
+    );
+
+    let name_width = stats
+        .iter()
+        .map(|s| s.name.chars().count())
+        .chain(std::iter::once(6))
+        .max()
+        .unwrap_or(6);
+    result.push_str(&format!(
+        "  # {:<width$} {:>7} {:>8}  Goals\n",
+        "Member",
+        "Total",
+        "Streak",
+        width = name_width
+    ));
+
+    for (idx, member) in stats.iter().enumerate() {
+        let goals = if member.goal_days == 0 {
+            "-".to_string()
+        } else {
+            format!(
+                "{}/{} ({}%)",
+                member.goals_reached,
+                member.goal_days,
+                member.goals_reached * 100 / member.goal_days
+            )
+        };
+        result.push_str(&format!(
+            "{:>3} {:<width$} {:>7} {:>3} days  {}\n",
+            idx + 1,
+            member.name,
+            member.weighted_total,
+            member.streak,
+            goals,
+            width = name_width
+        ));
+    }
+
+    result
+}
+
+//
+// Internals -----------------------------------
+//
+
+/// Number of consecutive days with activities, ending on `end` or the day before
+fn streak(by_day: &HashMap<Date<Local>, DayStats>, end: &Date<Local>) -> u32 {
+    let is_active = |day: &Date<Local>| {
+        by_day
+            .get(day)
+            .map(|stats| stats.reps_by_category.values().any(|reps| *reps > 0))
+            .unwrap_or(false)
+    };
+
+    let mut day = if is_active(end) { *end } else { end.pred() };
+    let mut streak = 0;
+    while is_active(&day) {
+        streak += 1;
+        day = day.pred();
+    }
+
+    streak
+}
+
+//
+// Tests ---------------------------------------------------
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Activity, Category};
+    use crate::storage::memory::MemoryStorage;
+    use chrono::TimeZone;
+
+    #[test]
+    fn leaderboard() {
+        let mut pushups = Category::new("Pushups", 1.0, vec!["pu"]);
+        pushups.goal = Some(20);
+        let alice = MemoryStorage::new(vec![pushups, Category::new("Burpees", 1.5, vec!["bu"])]);
+        let bob = MemoryStorage::new(vec![Category::new("Burpees", 3.0, vec!["bu"])]);
+
+        let end = Local.ymd(2021, 3, 14);
+        let start = end - chrono::Duration::days(6);
+        // alice: active on the three days before the end, goal reached twice
+        store(&alice, end.pred().pred().pred(), 20, "Pushups");
+        store(&alice, end.pred().pred(), 10, "Pushups");
+        store(&alice, end.pred(), 20, "Burpees");
+        store(&alice, end.pred(), 25, "Pushups");
+        // bob: the streak reaches back before the period, activities after it don't count
+        for days_ago in 0..10 {
+            store(&bob, end - chrono::Duration::days(days_ago), 5, "Burpees");
+        }
+        store(&bob, end.succ(), 100, "Burpees");
+
+        let config = Config::new("/nonexistent");
+        let members = vec![
+            Member {
+                name: "alice".to_string(),
+                storage: &alice,
+                config: &config,
+            },
+            Member {
+                name: "bob".to_string(),
+                storage: &bob,
+                config: &config,
+            },
+        ];
+
+        let stats = build(&members, None, &start, &end).unwrap();
+        assert_eq!(
+            vec![
+                MemberStats {
+                    name: "bob".to_string(),
+                    weighted_total: 7 * 15,
+                    streak: 10,
+                    goals_reached: 0,
+                    goal_days: 0,
+                },
+                MemberStats {
+                    name: "alice".to_string(),
+                    weighted_total: 20 + 10 + 30 + 25,
+                    streak: 3,
+                    goals_reached: 2,
+                    goal_days: 7,
+                }
+            ],
+            stats
+        );
+
+        assert_eq!(
+            "\
+Team report for the past 7 days (2021-03-08 - 2021-03-14)
+
+  # Member   Total   Streak  Goals
+  1 bob        105  10 days  -
+  2 alice       85   3 days  2/7 (28%)
+",
+            format(&stats, &start, &end, &config)
+        );
+
+        // with shared weights, Burpees count the same for everybody
+        let mut shared = CategoryLookup::new();
+        shared
+            .add(Category::new("Burpees", 2.0, vec!["bu"]))
+            .unwrap();
+        let stats = build(&members, Some(&shared), &start, &end).unwrap();
+        assert_eq!(
+            vec![("alice", 95), ("bob", 70)],
+            stats
+                .iter()
+                .map(|s| (s.name.as_str(), s.weighted_total))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    fn store(storage: &MemoryStorage, day: Date<Local>, reps: u32, category: &str) {
+        storage
+            .store(&Activity {
+                timestamp: day.and_hms(12, 0, 0),
+                reps,
+                category: category.to_string(),
+            })
+            .unwrap();
+    }
+}
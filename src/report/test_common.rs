@@ -1,8 +1,9 @@
-use crate::model::{Activity, Category, CategoryLookup};
+use crate::model::{Activity, Category, CategoryLookup, Config};
 use crate::report::common::*;
-use chrono::Local;
+use chrono::{Local, NaiveDate, TimeZone};
 
 #[test]
+#[allow(clippy::vec_init_then_push)]
 fn weighted_total_basic() {
     let lookup = new_lookup();
 
@@ -14,7 +15,7 @@ fn weighted_total_basic() {
     activities.push(Activity::new(28, "Beers"));
 
     assert_eq!(
-        weighted_total(&activities, &lookup),
+        weighted_total(&activities, &lookup, &Config::new("/nonexistent")),
         (15.0 + ((20.0 + 13.0) * 1.5) + (3200.0 * 0.01) + 28.0) as u32
     );
 }
@@ -42,6 +43,69 @@ fn daystats_basic() {
     assert_eq!(*stats.reps_by_category.get("Steps").unwrap(), 1500);
 }
 
+#[test]
+fn weighted_total_archived() {
+    let mut lookup = new_lookup();
+    let mut archived = newcat("Lunges", 0.5);
+    archived.archived = true;
+    lookup.add(archived).unwrap();
+
+    let activities = vec![Activity::new(10, "Pushups"), Activity::new(30, "Lunges")];
+    assert_eq!(
+        weighted_total(&activities, &lookup, &Config::new("/nonexistent")),
+        10 + 15
+    );
+
+    let mut stats = DayStats::new(&Local::now().date());
+    stats.add(&Activity::new(30, "Lunges"));
+    assert_eq!(stats.reps_total(&lookup), 15);
+}
+
+#[test]
+fn weighted_total_weight_changes() {
+    let mut lookup = CategoryLookup::new();
+    let mut burpees = newcat("Burpees", 1.5);
+    burpees.change_weight(NaiveDate::from_ymd(2020, 7, 10), 1.2);
+    burpees.change_weight(NaiveDate::from_ymd(2020, 7, 20), 2.0);
+    lookup.add(burpees).unwrap();
+
+    let activities = vec![
+        activity(9, 10, "Burpees"),
+        activity(10, 10, "Burpees"),
+        activity(19, 10, "Burpees"),
+        activity(25, 10, "Burpees"),
+    ];
+    assert_eq!(
+        weighted_total(&activities, &lookup, &Config::new("/nonexistent")),
+        15 + 12 + 12 + 20
+    );
+
+    let mut stats = DayStats::new(&Local.ymd(2020, 7, 15));
+    stats.add(&activity(15, 10, "Burpees"));
+    assert_eq!(stats.reps_total(&lookup), 12);
+
+    // after midnight, but before the start of the day: still the weight of the day before
+    let mut config = Config::new("/nonexistent");
+    config.day_starts_at = 4;
+    let late = Activity {
+        timestamp: Local.ymd(2020, 7, 20).and_hms(2, 0, 0),
+        reps: 10,
+        category: "Burpees".to_string(),
+    };
+    let mut stats = DayStats::new(&config.day_of(&late.timestamp));
+    stats.add(&late);
+    assert_eq!(12, stats.reps_total(&lookup));
+    assert_eq!(12, weighted_total(&[late], &lookup, &config));
+}
+
+fn activity(day_of_month: u32, reps: u32, category: &str) -> Activity {
+    Activity {
+        timestamp: Local.ymd(2020, 7, day_of_month).and_hms(13, 45, 34),
+        reps,
+        category: category.to_string(),
+    }
+}
+
 fn newcat(name: &str, weight: f64) -> Category {
     Category::new(name, weight, Vec::<String>::new())
 }
@@ -1,79 +1,110 @@
-use crate::model::{Activity, CategoryLookup, Config};
-use crate::report::common;
-use crate::storage;
+use crate::error::Error;
+use crate::model::{Activity, CategoryLookup, Config, Plan};
+use crate::report::{common, DayReport};
+use crate::storage::{self, Storage};
+use chrono::{Date, Local};
 use itertools::Itertools;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 /// Print the report for today
-pub fn run(config: &Config) -> Result<(), String> {
-    let categories = storage::read_categories(config)?;
-    let activities = storage::read_today(config)?;
+pub fn run(storage: &dyn Storage, config: &Config) -> Result<(), Error> {
+    let plan = storage::read_plan(config)?;
+    let report = build(&config.today(), storage, plan.as_ref(), config)?;
 
-    println!("\n{}", report(&activities, &categories));
+    println!("\n{}", format(&report));
     Ok(())
 }
 
-//
-// Internals -----------------------------
-//
-
-/// Generate the report for today as string
-fn report(activities: &[Activity], categories: &CategoryLookup) -> String {
-    let mut by_category = HashMap::new();
-    let mut individual: HashMap<String, Vec<u32>> = HashMap::new();
-    let total = common::weighted_total(activities, categories);
-
-    for activity in activities {
-        let cat = &activity.category;
-
-        if by_category.contains_key(cat) {
-            let parts = individual.get_mut(cat).unwrap();
-            parts.push(activity.reps);
-            let sum = by_category.get(cat).unwrap() + activity.reps;
-            by_category.insert(cat.to_string(), sum);
-        } else {
-            let mut parts = Vec::new();
-            parts.push(activity.reps);
-            individual.insert(cat.to_string(), parts);
-
-            by_category.insert(cat.to_string(), activity.reps);
-        };
-    }
+/// Collect the data for the report on the given day
+pub fn build(
+    day: &Date<Local>,
+    storage: &dyn Storage,
+    plan: Option<&Plan>,
+    config: &Config,
+) -> Result<DayReport, Error> {
+    let categories = storage.read_categories()?;
+    let activities = storage.read_days(day, day)?;
+
+    let mut report = aggregate(day, &activities, &categories, config);
+    report.targets = categories.targets_on(day.naive_local(), plan);
+    Ok(report)
+}
 
+/// Format the given report on a single day as string
+pub fn format(report: &DayReport) -> String {
     let mut result = String::new();
 
     result.push_str("Stats for today:\n");
-    for category in by_category.keys().sorted() {
-        let reps = by_category.get(category).unwrap();
-
-        let details = if individual.get(category).unwrap().len() > 1 {
-            format!(
-                " ({})",
-                individual.get(category).unwrap().iter().join(" + ")
-            )
-        } else {
-            "".to_string()
+    let categories = report
+        .sets_by_category
+        .keys()
+        .chain(report.targets.keys())
+        .collect::<BTreeSet<_>>();
+    for category in categories {
+        let details = match report.sets_by_category.get(category) {
+            Some(sets) if sets.len() > 1 => format!(" ({})", sets.iter().join(" + ")),
+            _ => "".to_string(),
+        };
+        let target = match report.targets.get(category) {
+            Some(target) => format!(" of {}", target),
+            None => "".to_string(),
         };
 
-        result.push_str(&format!("  {:<15}: {} reps{}\n", category, reps, details));
+        result.push_str(&format!(
+            "  {:<15}: {}{} reps{}\n",
+            category,
+            report.reps(category),
+            target,
+            details
+        ));
     }
 
-    if by_category.len() > 1 {
-        result.push_str(&format!("  Weighted total : {}", total));
+    if report.sets_by_category.len() > 1 {
+        result.push_str(&format!("  Weighted total : {}", report.weighted_total));
     }
 
     result
 }
 
+//
+// Internals -----------------------------
+//
+
+/// Aggregate the given activities of a single day
+fn aggregate(
+    day: &Date<Local>,
+    activities: &[Activity],
+    categories: &CategoryLookup,
+    config: &Config,
+) -> DayReport {
+    let mut sets_by_category: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+
+    for activity in activities {
+        sets_by_category
+            .entry(activity.category.to_string())
+            .or_default()
+            .push(activity.reps);
+    }
+
+    DayReport {
+        day: *day,
+        sets_by_category,
+        weighted_total: common::weighted_total(activities, categories, config),
+        targets: BTreeMap::new(),
+    }
+}
+
 //
 // Tests ------------------------------------
 //
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::model::{Activity, Category, CategoryLookup};
+    use crate::model::{Activity, Category, CategoryLookup, Increase, PlanSchedule, Schedule};
+    use chrono::{NaiveDate, TimeZone};
 
     #[test]
+    #[allow(clippy::vec_init_then_push)]
     fn basic() {
         let mut lookup = CategoryLookup::new();
 
@@ -88,7 +119,12 @@ mod tests {
         activities.push(Activity::new(3200, "Steps"));
         activities.push(Activity::new(28, "Beers"));
 
-        let report = report(&activities, &lookup);
+        let report = format(&aggregate(
+            &Local::today(),
+            &activities,
+            &lookup,
+            &Config::new("/nonexistent"),
+        ));
 
         assert_eq!(
             report,
@@ -102,6 +138,86 @@ Stats for today:
         );
     }
 
+    #[test]
+    fn plan_targets() {
+        let mut lookup = CategoryLookup::new();
+        let mut pushups = newcat("Pushups", 1.0);
+        pushups.goal = Some(100);
+        let mut squats = newcat("Squats", 1.0);
+        squats.goal = Some(50);
+        let mut burpees = newcat("Burpees", 1.5);
+        burpees.goal = Some(30);
+        burpees.archived = true;
+        lookup.add(pushups).unwrap();
+        lookup.add(squats).unwrap();
+        lookup.add(burpees).unwrap();
+
+        let plan = Plan {
+            start: NaiveDate::from_ymd(2021, 3, 1),
+            schedules: vec![
+                PlanSchedule {
+                    category: "Pushups".to_string(),
+                    schedule: Schedule::Weekly {
+                        initial: 20,
+                        increase: Increase::Percent(10),
+                        weeks: 2,
+                    },
+                },
+                PlanSchedule {
+                    category: "Squats".to_string(),
+                    schedule: Schedule::Days(vec![30, 0]),
+                },
+            ],
+        };
+        let targets = |day_of_month: u32, plan: Option<&Plan>| {
+            lookup
+                .targets_on(NaiveDate::from_ymd(2021, 3, day_of_month), plan)
+                .into_iter()
+                .collect::<Vec<_>>()
+        };
+        let expect = |targets: &[(&str, u32)]| {
+            targets
+                .iter()
+                .map(|(category, target)| (category.to_string(), *target))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            expect(&[("Pushups", 100), ("Squats", 50)]),
+            targets(1, None)
+        );
+        assert_eq!(
+            expect(&[("Pushups", 100), ("Squats", 50)]),
+            targets(28, Some(&plan))
+        );
+        assert_eq!(
+            expect(&[("Pushups", 20), ("Squats", 30)]),
+            targets(1, Some(&plan))
+        );
+        // rest day
+        assert_eq!(expect(&[("Pushups", 20)]), targets(2, Some(&plan)));
+        assert_eq!(
+            expect(&[("Pushups", 22), ("Squats", 50)]),
+            targets(8, Some(&plan))
+        );
+
+        let mut report = aggregate(
+            &Local.ymd(2021, 3, 1),
+            &[Activity::new(15, "Pushups"), Activity::new(10, "Pushups")],
+            &lookup,
+            &Config::new("/nonexistent"),
+        );
+        report.targets = lookup.targets_on(report.day.naive_local(), Some(&plan));
+        assert_eq!(
+            "\
+Stats for today:
+  Pushups        : 25 of 20 reps (15 + 10)
+  Squats         : 0 of 30 reps
+",
+            format(&report)
+        );
+    }
+
     fn newcat(name: &str, weight: f64) -> Category {
         Category::new(name, weight, Vec::<String>::new())
     }
@@ -1,40 +1,232 @@
-use crate::model::{Activity, CategoryLookup, Config};
+use crate::check::Finding;
+use crate::error::Error;
+use crate::model::{Activity, Category, CategoryLookup, Challenge, Config, Plan, StorageBackend};
 use chrono::{Date, Local};
+use std::path::Path;
 
 mod fs;
+#[cfg(test)]
+pub mod memory;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+pub use fs::{Backup, MergedConflict};
 
 //
 // Main Interface -----------------------
 //
-pub fn store(activity: &Activity, config: &Config) -> Result<(), String> {
-    match fs::store(activity, config) {
-        Ok(_) => Ok(()),
-        Err(error) => Err(format!("{:?}", error)),
+
+/// Interface of all storage backends for activities and categories
+pub trait Storage {
+    /// Store the given activity
+    fn store(&self, activity: &Activity) -> Result<(), Error>;
+
+    /// Store all given activities
+    fn store_all(&self, activities: &[Activity]) -> Result<(), Error> {
+        for activity in activities {
+            self.store(activity)?;
+        }
+
+        Ok(())
+    }
+
+    /// Read all activities for the given range of days (both ends inclusive)
+    fn read_days(&self, start: &Date<Local>, end: &Date<Local>) -> Result<Vec<Activity>, Error>;
+
+    /// Read all stored activities, sorted by timestamp
+    fn read_all(&self) -> Result<Vec<Activity>, Error>;
+
+    /// Read all categories and return a populated lookup structure
+    fn read_categories(&self) -> Result<CategoryLookup, Error>;
+
+    /// Add a new category
+    fn add_category(&self, category: &Category) -> Result<(), Error>;
+
+    /// Replace all stored categories with the given ones
+    fn write_categories(&self, categories: &[Category]) -> Result<(), Error>;
+
+    /// Delete the given (previously stored) activity
+    fn delete(&self, activity: &Activity) -> Result<(), Error>;
+
+    /// Replace the given (previously stored) activity with an updated version
+    fn update(&self, old: &Activity, new: &Activity) -> Result<(), Error>;
+}
+
+/// Open the storage backend selected in the given config
+pub fn open(config: &Config) -> Result<Box<dyn Storage>, Error> {
+    match config.storage_backend {
+        StorageBackend::Text => Ok(Box::new(fs::TextStorage::new(config))),
+        #[cfg(feature = "sqlite")]
+        StorageBackend::Sqlite => Ok(Box::new(sqlite::SqliteStorage::open(config)?)),
+        #[cfg(not(feature = "sqlite"))]
+        StorageBackend::Sqlite => Err(Error::Storage {
+            msg: "This build of naday does not support the SQLite backend".to_string(),
+        }),
     }
 }
 
-pub fn read_today(config: &Config) -> Result<Vec<Activity>, String> {
-    match fs::read_today(config) {
-        Ok(activities) => Ok(activities),
-        Err(error) => Err(format!("{:?}", error)),
+/// Open the storage backend selected in the given config for reading only, e.g. for the data
+/// directory of a teammate: Nothing in the directory is created, upgraded or locked.
+pub fn open_read_only(config: &Config) -> Result<Box<dyn Storage>, Error> {
+    match config.storage_backend {
+        StorageBackend::Text => Ok(Box::new(fs::TextStorage::read_only(config))),
+        #[cfg(feature = "sqlite")]
+        StorageBackend::Sqlite => Ok(Box::new(sqlite::SqliteStorage::open_read_only(config)?)),
+        #[cfg(not(feature = "sqlite"))]
+        StorageBackend::Sqlite => Err(Error::Storage {
+            msg: "This build of naday does not support the SQLite backend".to_string(),
+        }),
     }
 }
 
-pub fn read_days(
-    start: &Date<Local>,
-    end: &Date<Local>,
-    config: &Config,
-) -> Result<Vec<Activity>, String> {
-    match fs::read_days(start, end, config) {
-        Ok(activities) => Ok(activities),
-        Err(error) => Err(format!("{:?}", error)),
+/// Remove all categories and activities of the storage backend selected in the given config. Other
+/// files in the data directory (config, challenges, ...) are kept.
+pub fn remove_data(config: &Config) -> Result<(), Error> {
+    match config.storage_backend {
+        StorageBackend::Text => Ok(fs::remove_data_files(config)?),
+        #[cfg(feature = "sqlite")]
+        StorageBackend::Sqlite => Ok(sqlite::SqliteStorage::remove(config)?),
+        #[cfg(not(feature = "sqlite"))]
+        StorageBackend::Sqlite => Ok(()),
     }
 }
 
-/// Read all categories and return a populated lookup structure
-pub fn read_categories(cfg: &Config) -> Result<CategoryLookup, String> {
-    match fs::read_categories(cfg) {
-        Ok(lookup) => Ok(lookup),
-        Err(error) => Err(format!("{:?}", error)),
+/// Copy all categories and activities from `source` to `target`. The categories in `target` are
+/// replaced, the activities are added to the existing ones.
+///
+/// # Returns
+/// The number of copied categories and activities
+pub fn copy_all(source: &dyn Storage, target: &dyn Storage) -> Result<(usize, usize), Error> {
+    let categories: Vec<Category> = source
+        .read_categories()?
+        .iter()
+        .map(|category| category.as_ref().clone())
+        .collect();
+    target.write_categories(&categories)?;
+
+    let activities = source.read_all()?;
+    target.store_all(&activities)?;
+
+    Ok((categories.len(), activities.len()))
+}
+
+/// Upgrade all text files in the data directory to the current format versions in place. Reading
+/// works without this, outdated files are upgraded in memory.
+///
+/// # Returns
+/// The number of upgraded files
+pub fn upgrade_data_dir(cfg: &Config) -> Result<usize, Error> {
+    Ok(fs::upgrade_data_dir(cfg)?)
+}
+
+/// Scan the data files of the text backend for unreadable or misplaced entries, optionally
+/// fixing them
+pub fn check_files(cfg: &Config, fix: bool) -> Result<Vec<Finding>, Error> {
+    Ok(fs::check_files(cfg, fix)?)
+}
+
+/// Read the categories from a category file in the format of the text backend
+pub fn read_category_file(path: &Path) -> Result<CategoryLookup, Error> {
+    Ok(fs::read_category_file(path)?)
+}
+
+/// Read all challenges. They are kept in a text file in the data directory, whatever the backend.
+pub fn read_challenges(cfg: &Config) -> Result<Vec<Challenge>, Error> {
+    Ok(fs::read_challenges(cfg)?)
+}
+
+/// Add a challenge to the challenge file
+pub fn add_challenge(cfg: &Config, challenge: &Challenge) -> Result<(), Error> {
+    Ok(fs::append_challenge(challenge, cfg)?)
+}
+
+/// Read the training plan, if there is one. Like challenges, it is kept in a text file.
+pub fn read_plan(cfg: &Config) -> Result<Option<Plan>, Error> {
+    Ok(fs::read_plan(cfg)?)
+}
+
+/// Read a training plan from a file outside of the data directory
+pub fn read_plan_file(path: &Path) -> Result<Plan, Error> {
+    Ok(fs::read_plan_file(path)?)
+}
+
+/// Replace the training plan, or remove it
+pub fn write_plan(cfg: &Config, plan: Option<&Plan>) -> Result<(), Error> {
+    Ok(fs::write_plan(cfg, plan)?)
+}
+
+/// Merge the sync conflict copies of the text files in the data directory into the original
+/// files and archive them
+pub fn merge_conflicts(cfg: &Config) -> Result<Vec<MergedConflict>, Error> {
+    Ok(fs::merge_conflicts(cfg)?)
+}
+
+/// Snapshot all files in the data directory. Automatic backups (any `reason` but "manual") are
+/// rotated, keeping the configured number.
+pub fn create_backup(cfg: &Config, reason: &str) -> Result<Backup, Error> {
+    Ok(fs::create_backup(cfg, reason)?)
+}
+
+/// Create the daily backup if there is none from today yet
+pub fn create_daily_backup(cfg: &Config) -> Result<Option<Backup>, Error> {
+    Ok(fs::create_daily_backup(cfg)?)
+}
+
+/// All backups of the data directory, oldest first
+pub fn list_backups(cfg: &Config) -> Result<Vec<Backup>, Error> {
+    Ok(fs::list_backups(cfg)?)
+}
+
+/// Replace the files in the data directory with the given backup, backing up the current state
+/// before
+pub fn restore_backup(cfg: &Config, id: &str) -> Result<Backup, Error> {
+    Ok(fs::restore_backup(cfg, id)?)
+}
+
+/// Apply the settings from the config file in the data directory to the given config
+pub fn read_config(cfg: &mut Config) -> Result<(), Error> {
+    Ok(fs::read_config(cfg)?)
+}
+
+/// Apply the settings from the config file in the data directory without creating or upgrading
+/// it. Without a config file, the given config is kept as it is.
+pub fn read_config_read_only(cfg: &mut Config) -> Result<(), Error> {
+    Ok(fs::read_config_read_only(cfg)?)
+}
+
+/// Change a single setting in the config file in the data directory
+pub fn write_setting(cfg: &Config, name: &str, value: &str) -> Result<(), Error> {
+    Ok(fs::write_setting(cfg, name, value)?)
+}
+
+//
+// Tests ---------------------------------
+//
+#[cfg(test)]
+mod tests {
+    use super::memory::MemoryStorage;
+    use super::*;
+
+    #[test]
+    fn copy_all_lossless() {
+        let mut burpees = Category::new("Burpees", 1.5, vec!["bu"]);
+        burpees.archived = true;
+        let source = MemoryStorage::new(vec![Category::new("Pushups", 1.0, vec!["pu"]), burpees]);
+        source.store(&Activity::new(10, "Pushups")).unwrap();
+        source.store(&Activity::new(5, "Burpees")).unwrap();
+
+        let target = MemoryStorage::new(vec![Category::new("Situps", 1.0, vec!["si"])]);
+        assert_eq!((2, 2), copy_all(&source, &target).unwrap());
+
+        assert_eq!(source.read_all().unwrap(), target.read_all().unwrap());
+        let names = |storage: &MemoryStorage| -> Vec<String> {
+            storage
+                .read_categories()
+                .unwrap()
+                .iter()
+                .map(|c| c.to_string())
+                .collect()
+        };
+        assert_eq!(names(&source), names(&target));
     }
 }
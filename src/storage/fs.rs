@@ -1,36 +1,191 @@
-use crate::model::{Activity, CategoryLookup, Config};
+use super::Storage;
+use crate::check::Finding;
+use crate::error::Error;
+use crate::model::{Activity, Category, CategoryLookup, Config};
 
 use anyhow::{Context, Result};
 use chrono::prelude::*;
-use std::path::PathBuf;
+use format::FileKind;
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
 
 mod activity;
+mod backup;
 mod category;
+mod challenge;
+mod config;
+mod conflicts;
+mod format;
+mod lock;
+mod plan;
 
-/// Store the given activity on the filesystem
-pub fn store(activity: &Activity, config: &Config) -> Result<()> {
-    activity::store(activity, config)
+pub use backup::{
+    create as create_backup, create_daily as create_daily_backup, list as list_backups,
+    restore as restore_backup, Backup,
+};
+#[cfg(feature = "sqlite")]
+pub use category::default_categories;
+pub use challenge::{append_challenge, read_challenges};
+pub use conflicts::{merge_conflicts, MergedConflict};
+pub use plan::{read_plan, read_plan_file, write_plan};
+
+/// Storage backend using plain text files in the data directory: One file per month for the
+/// activities, plus one file for the categories. Outdated files are only upgraded in memory while
+/// reading, and written back in the current format before the first change.
+pub struct TextStorage {
+    config: Config,
+    upgraded: Cell<bool>,
+    read_only: bool,
+}
+
+impl TextStorage {
+    pub fn new(config: &Config) -> TextStorage {
+        TextStorage {
+            config: config.clone(),
+            upgraded: Cell::new(false),
+            read_only: false,
+        }
+    }
+
+    /// Storage that never writes to the data directory, not even to initialize missing files
+    pub fn read_only(config: &Config) -> TextStorage {
+        TextStorage {
+            read_only: true,
+            ..TextStorage::new(config)
+        }
+    }
+
+    /// Upgrade the data directory once, before writing to it
+    fn upgrade(&self) -> Result<(), Error> {
+        if self.read_only {
+            return Err(Error::Storage {
+                msg: format!("{} is opened read-only", &self.config.data_dir),
+            });
+        }
+        if !self.upgraded.get() {
+            upgrade_data_dir(&self.config)?;
+            self.upgraded.set(true);
+        }
+
+        Ok(())
+    }
+}
+
+impl Storage for TextStorage {
+    fn store(&self, activity: &Activity) -> Result<(), Error> {
+        self.upgrade()?;
+        activity::store(activity, &self.config).map_err(Error::from)
+    }
+
+    fn read_days(&self, start: &Date<Local>, end: &Date<Local>) -> Result<Vec<Activity>, Error> {
+        activity::read_days(start, end, &self.config).map_err(Error::from)
+    }
+
+    fn read_all(&self) -> Result<Vec<Activity>, Error> {
+        activity::read_all(&self.config).map_err(Error::from)
+    }
+
+    fn read_categories(&self) -> Result<CategoryLookup, Error> {
+        let categories = if self.read_only {
+            category::read_categories_read_only(&self.config)
+        } else {
+            category::read_categories(&self.config)
+        };
+
+        categories.and_then(to_lookup).map_err(Error::from)
+    }
+
+    fn add_category(&self, category: &Category) -> Result<(), Error> {
+        self.upgrade()?;
+        category::append_category(category, &self.config).map_err(Error::from)
+    }
+
+    fn write_categories(&self, categories: &[Category]) -> Result<(), Error> {
+        self.upgrade()?;
+        category::write_categories(categories, &self.config).map_err(Error::from)
+    }
+
+    fn delete(&self, activity: &Activity) -> Result<(), Error> {
+        self.upgrade()?;
+        activity::delete(activity, &self.config).map_err(Error::from)
+    }
+
+    fn update(&self, old: &Activity, new: &Activity) -> Result<(), Error> {
+        self.upgrade()?;
+        activity::update(old, new, &self.config).map_err(Error::from)
+    }
+}
+
+/// Upgrade all files in the data directory to the current format versions, keeping a backup of
+/// the originals
+///
+/// # Returns
+/// The number of upgraded files
+pub fn upgrade_data_dir(cfg: &Config) -> Result<usize> {
+    let dir = Path::new(&cfg.data_dir);
+    let mut files = vec![
+        (dir.join(config::CONFIG_FILE), FileKind::Config),
+        (dir.join(category::CATEGORY_FILE), FileKind::Categories),
+        (dir.join(challenge::CHALLENGE_FILE), FileKind::Challenges),
+        (dir.join(plan::PLAN_FILE), FileKind::Plan),
+    ];
+    for path in activity::activity_files(cfg)? {
+        files.push((path, FileKind::Activities));
+    }
+
+    format::upgrade_files(&files, cfg)
+}
+
+/// Remove the activity files and the category file, e.g. after migrating to another backend
+pub fn remove_data_files(cfg: &Config) -> Result<()> {
+    let _lock = lock::lock(cfg)?;
+    let category_file = Path::new(&cfg.data_dir).join(category::CATEGORY_FILE);
+
+    for path in activity::activity_files(cfg)?
+        .into_iter()
+        .chain(std::iter::once(category_file))
+        .filter(|path| path.exists())
+    {
+        std::fs::remove_file(&path).with_context(|| format!("Could not remove {:?}", &path))?;
+    }
+
+    Ok(())
 }
 
-/// Read all activities for a given day
-pub fn read_day(date: &Date<Local>, config: &Config) -> Result<Vec<Activity>> {
-    activity::read_day(date, config)
+/// Scan the category file and all activity files for unreadable, duplicate or misplaced entries,
+/// optionally fixing the activity files
+pub fn check_files(cfg: &Config, fix: bool) -> Result<Vec<Finding>> {
+    let mut findings = category::check_file(cfg)?;
+    findings.extend(activity::check_files(cfg, fix)?);
+    Ok(findings)
 }
 
-/// Read all activities for today
-pub fn read_today(config: &Config) -> Result<Vec<Activity>> {
-    let now = Local::today();
-    read_day(&now, config)
+/// Apply the settings from the config file in the data directory to the given config
+pub fn read_config(cfg: &mut Config) -> Result<()> {
+    config::read_config(cfg)
 }
 
-/// Read all activities for the given range of days (both ends inclusive)
-pub fn read_days(start: &Date<Local>, end: &Date<Local>, config: &Config) -> Result<Vec<Activity>> {
-    activity::read_days(start, end, config)
+/// Like `read_config`, but without creating the config file if it is missing
+pub fn read_config_read_only(cfg: &mut Config) -> Result<()> {
+    config::read_config_read_only(cfg)
 }
 
-/// Read all categories and return a populated lookup structure
-pub fn read_categories(cfg: &Config) -> Result<CategoryLookup> {
-    let categories = category::read_categories(cfg)?;
+/// Change a single setting in the config file in the data directory
+pub fn write_setting(cfg: &Config, name: &str, value: &str) -> Result<()> {
+    config::write_setting(cfg, name, value)
+}
+
+/// Read a category file outside of a data directory, e.g. one shared by a team
+pub fn read_category_file(path: &Path) -> Result<CategoryLookup> {
+    to_lookup(category::read_category_file(path)?)
+}
+
+//
+// Internals --------------------------------------
+//
+
+/// Populate a lookup structure with the given categories
+fn to_lookup(categories: Vec<Category>) -> Result<CategoryLookup> {
     let mut lookup = CategoryLookup::new();
 
     for category in categories {
@@ -40,10 +195,6 @@ pub fn read_categories(cfg: &Config) -> Result<CategoryLookup> {
     Ok(lookup)
 }
 
-//
-// Internals --------------------------------------
-//
-
 fn init_data_dir(cfg: &Config) -> Result<PathBuf> {
     let path = PathBuf::from(&cfg.data_dir);
     std::fs::create_dir_all(&path)
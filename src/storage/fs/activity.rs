@@ -1,10 +1,16 @@
 use crate::model::{Activity, Config};
 
-use crate::error::ParseError;
+use super::format::{self, FileKind};
+use super::lock;
+use crate::check::{Finding, Problem};
+use crate::error::Error;
 use anyhow::{bail, Context, Result};
 use chrono::prelude::*;
+use chrono::{Duration, LocalResult};
 use itertools::Itertools;
+use lazy_static::lazy_static;
 use log::debug;
+use regex::Regex;
 use std::fs;
 use std::fs::{File, OpenOptions};
 use std::io::Write;
@@ -14,39 +20,52 @@ type ActivitiesOrError = Result<Vec<Activity>>;
 
 /// Store the given activity on the filesystem
 pub fn store(activity: &Activity, config: &Config) -> Result<()> {
-    let dir_path = super::init_data_dir(&config);
+    let file_path = path_for_activity(activity, config);
+    init_activity_file(&file_path, config)?;
 
-    let file_path = path_for_date(&activity.timestamp.date(), config);
-
-    let mut file: File = init_activity_file(&file_path)
-        .with_context(|| format!("Activity file {:?} could not be initialized", &dir_path))?;
+    let _lock = lock::lock(config)?;
+    append_activity(&file_path, activity)
+}
 
-    writeln!(
-        &mut file,
-        "{};{};{}",
-        ts2str(activity.timestamp),
-        activity.reps,
-        activity.category
-    )
-    .with_context(|| format!("Could not write activity to file {:?}", &dir_path))?;
+/// Delete the given activity from its activity file
+pub fn delete(activity: &Activity, config: &Config) -> Result<()> {
+    let path = path_for_activity(activity, config);
 
-    Ok(())
+    let _lock = lock::lock(config)?;
+    replace_activity(&path, activity, None)
 }
 
-/// Read all activities for a given day
-pub fn read_day(date: &Date<Local>, config: &Config) -> ActivitiesOrError {
-    read_days(date, date, config)
+/// Replace the given activity with an updated version. If the new version belongs to another
+/// month, it is moved to the corresponding file.
+pub fn update(old: &Activity, new: &Activity, config: &Config) -> Result<()> {
+    let old_path = path_for_activity(old, config);
+    let new_path = path_for_activity(new, config);
+
+    if old_path == new_path {
+        let _lock = lock::lock(config)?;
+        replace_activity(&old_path, old, Some(new))
+    } else {
+        init_activity_file(&new_path, config)?;
+
+        let _lock = lock::lock(config)?;
+        replace_activity(&old_path, old, None)?;
+        append_activity(&new_path, new)
+    }
 }
 
-/// Read all activities for the days from "start" up to "end" (inclusive)
+/// Read all activities for the days from "start" up to "end" (inclusive) in the home time zone
 pub fn read_days(start: &Date<Local>, end: &Date<Local>, config: &Config) -> ActivitiesOrError {
     if end < start {
-        panic!("end is before start");
+        bail!(Error::validation(
+            "The end of the range is before its start"
+        ));
     }
 
+    // activities near the boundaries may be in the files of the neighbouring days' months, e.g.
+    // after the home time zone was changed
     let mut paths = Vec::new();
-    let mut day = *start;
-    while day <= *end {
+    let mut day = start.pred();
+    while day <= end.succ() {
         paths.push(path_for_date(&day, config));
         day = day.succ();
     }
@@ -64,20 +83,197 @@ pub fn read_days(start: &Date<Local>, end: &Date<Local>, config: &Config) -> Act
     debug!("Found {} activities in input files", activities.len());
     let activities: Vec<Activity> = activities
         .into_iter()
-        .filter(|a| &a.timestamp.date() >= start && &a.timestamp.date() <= end)
+        .filter(|a| {
+            let day = config.day_of(&a.timestamp);
+            &day >= start && &day <= end
+        })
         .collect();
 
     debug!("Found {} activities in time range", activities.len());
     Ok(activities)
 }
 
+/// Read all activities in all activity files, sorted by timestamp
+pub fn read_all(config: &Config) -> ActivitiesOrError {
+    let mut activities = Vec::new();
+
+    for path in activity_files(config)? {
+        debug!("Reading path {:?}", &path);
+        let mut for_path = read_activities(&path)
+            .with_context(|| format!("Unable to read activities from file {:?}", &path))?;
+        activities.append(&mut for_path);
+    }
+
+    activities.sort_by_key(|a| a.timestamp);
+    Ok(activities)
+}
+
+/// Merge a (sync) conflict copy into the given activity file: All activities of the copy that are
+/// not contained in the file yet are appended. Expects the caller to hold the lock.
+///
+/// # Returns
+/// The number of merged activities
+pub fn merge_file(target: &Path, copy: &Path) -> Result<usize> {
+    let mut known = read_activities(target)
+        .with_context(|| format!("Unable to read activities from file {:?}", target))?;
+    let theirs = read_activities(copy)
+        .with_context(|| format!("Unable to read activities from file {:?}", copy))?;
+
+    let mut contents = match fs::read_to_string(target) {
+        Ok(contents) => format::upgrade(&contents, FileKind::Activities, target)?,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => file_header(),
+        Err(error) => bail!(error),
+    };
+    if !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+
+    let mut merged = 0;
+    for activity in theirs {
+        if !known.contains(&activity) {
+            contents.push_str(&format_activity(&activity));
+            contents.push('\n');
+            known.push(activity);
+            merged += 1;
+        }
+    }
+
+    if merged > 0 {
+        lock::write_atomic(target, &contents)
+            .with_context(|| format!("Could not write activities to file {:?}", target))?;
+    }
+    Ok(merged)
+}
+
+/// Scan all activity files for lines that can't be used as they are: Unreadable lines and entries
+/// stored in the file of another month. With `fix`, unreadable lines are commented out and
+/// misplaced entries are moved to the right file.
+pub fn check_files(config: &Config, fix: bool) -> Result<Vec<Finding>> {
+    let _lock = if fix { Some(lock::lock(config)?) } else { None };
+    let mut findings = Vec::new();
+    let mut misplaced = Vec::new();
+
+    for path in activity_files(config)? {
+        let file = path.display().to_string();
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Unable to read activities from file {:?}", &path))?;
+
+        let upgraded = match format::upgrade(&contents, FileKind::Activities, &path) {
+            Ok(upgraded) => upgraded,
+            Err(error) => {
+                let msg = match Error::from(error) {
+                    Error::Parse { msg, .. } => msg,
+                    error => error.to_string(),
+                };
+                findings.push(Finding::new(Problem::UnreadableFile { file, msg }, false));
+                continue;
+            }
+        };
+
+        let mut lines = upgraded.lines();
+        let mut result = format!("{}\n", lines.next().unwrap_or_default());
+
+        // the preamble is line 1
+        for (line_no, line) in lines.enumerate().map(|(idx, line)| (idx + 2, line)) {
+            let trimmed = line.trim();
+            if trimmed.starts_with('#') || trimmed.is_empty() {
+                result.push_str(line);
+            } else {
+                match parse_activity(trimmed) {
+                    Err(_) => {
+                        let problem = Problem::MalformedLine {
+                            file: file.clone(),
+                            line: line_no,
+                            content: trimmed.to_string(),
+                        };
+                        findings.push(Finding::new(problem, fix));
+                        result.push_str(&format!(
+                            "{}{}",
+                            if fix { "# unreadable: " } else { "" },
+                            line
+                        ));
+                    }
+                    Ok(activity) if path_for_activity(&activity, config) != path => {
+                        let problem = Problem::WrongFile {
+                            file: file.clone(),
+                            line: line_no,
+                            activity: activity.clone(),
+                        };
+                        findings.push(Finding::new(problem, fix));
+                        if fix {
+                            misplaced.push(activity);
+                            continue;
+                        }
+                        result.push_str(line);
+                    }
+                    Ok(_) => result.push_str(line),
+                }
+            }
+            result.push('\n');
+        }
+
+        if fix && result != contents {
+            lock::write_atomic(&path, &result)
+                .with_context(|| format!("Could not write activities to file {:?}", &path))?;
+        }
+    }
+
+    for activity in misplaced {
+        let path = path_for_activity(&activity, config);
+        if !path.exists() {
+            lock::write_atomic(&path, file_header())?;
+        }
+        append_activity(&path, &activity)?;
+    }
+
+    Ok(findings)
+}
+
+/// Format upgrade v1 -> v2: Timestamps get an explicit UTC offset. Timestamps without one are
+/// interpreted in the local time zone, lines that can't be parsed are kept as they are.
+pub fn add_utc_offsets(body: &str) -> Result<String> {
+    let mut result = String::new();
+
+    for line in body.lines() {
+        let trimmed = line.trim();
+        if trimmed == V1_COLUMNS_COMMENT {
+            result.push_str(COLUMNS_COMMENT);
+        } else if trimmed.starts_with('#') || trimmed.is_empty() {
+            result.push_str(line);
+        } else {
+            match parse_activity(trimmed) {
+                Ok(activity) => result.push_str(&format_activity(&activity)),
+                Err(_) => result.push_str(line),
+            }
+        }
+        result.push('\n');
+    }
+
+    Ok(result)
+}
+
+/// Whether the given file name is the name of an activity file
+pub fn is_activity_file(file_name: &str) -> bool {
+    ACTIVITY_FILE_PATTERN.is_match(file_name)
+}
+
 //
 // Internals ------------------------------
 //
 
+lazy_static! {
+    static ref ACTIVITY_FILE_PATTERN: Regex = Regex::new(r"^\d{4}-\d{2}\.txt$").unwrap();
+}
+
 const ACTIVITY_FILE_FORMAT: &str = "%Y-%m.txt";
-const ACTIVITY_TS_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
-const PREAMBLE_ACTIVITIES_V1: &str = "naday activities v1";
+const ACTIVITY_TS_FORMAT: &str = "%Y-%m-%d %H:%M:%S%:z";
+/// Timestamps without UTC offset, as written before format v2
+const LEGACY_TS_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+const COLUMNS_COMMENT: &str =
+    "# Columns: timestamp (with UTC offset) ; number of repetitions ; category (excercise)";
+const V1_COLUMNS_COMMENT: &str =
+    "# Columns: timestamp (local time zone) ; number of repetitions ; category (excercise)";
 
 /// Get the path (fully qualified filename) of the file containing the activities of the given
 /// date.
@@ -88,34 +284,73 @@ fn path_for_date(date: &Date<Local>, config: &Config) -> PathBuf {
     file_path
 }
 
-/// Open the activity file for the given timestamp.
-/// If it doesn't exist, initialize it
-fn init_activity_file(path: &Path) -> Result<File> {
-    if path.exists() {
-        return Ok(OpenOptions::new().append(true).open(path)?);
+/// Get the path of the file the given activity belongs to: The file of the month its day (in the
+/// home time zone) is in
+fn path_for_activity(activity: &Activity, config: &Config) -> PathBuf {
+    path_for_date(&config.day_of(&activity.timestamp), config)
+}
+
+/// Get the paths of all activity files in the data directory, sorted by name (and thus by month)
+pub fn activity_files(config: &Config) -> Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(&config.data_dir) {
+        Ok(entries) => entries,
+        Err(error) => match error.kind() {
+            std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            _ => bail!(error),
+        },
+    };
+
+    let mut paths = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if is_activity_file(&entry.file_name().to_string_lossy()) {
+            paths.push(entry.path());
+        }
     }
 
-    let mut file: File = OpenOptions::new().create(true).write(true).open(path)?;
+    paths.sort();
+    Ok(paths)
+}
+
+/// Initialize the given activity file if it doesn't exist yet
+fn init_activity_file(path: &Path, config: &Config) -> Result<()> {
+    lock::init_file(path, &file_header(), config)
+        .with_context(|| format!("Activity file {:?} could not be initialized", path))
+}
 
-    writeln!(
-        &mut file,
+/// Preamble and explanation at the start of each activity file
+fn file_header() -> String {
+    format!(
         "\
 {}
 # List of recorded activities for the 'naday' tool (https://github.com/creinig/naday)
 # Lines beginning with '#' are comments and are ignored by the tool
 # The remaining lines are plain CSV, with one recorded activity per line.
 # Separator character is ';', encoding is UTF-8.
-# Columns: timestamp (local time zone) ; number of repetitions ; category (excercise)",
-        PREAMBLE_ACTIVITIES_V1
-    )?;
+{}
+",
+        FileKind::Activities.preamble(),
+        COLUMNS_COMMENT
+    )
+}
+
+/// Append the given activity to an existing activity file. Expects the caller to hold the lock.
+fn append_activity(path: &Path, activity: &Activity) -> Result<()> {
+    let mut file: File = OpenOptions::new()
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Could not open activity file {:?} for writing", path))?;
 
-    Ok(file)
+    writeln!(&mut file, "{}", format_activity(activity))
+        .with_context(|| format!("Could not write activity to file {:?}", path))?;
+
+    Ok(())
 }
 
 /// Read all activities in the given file. If the file does not exist an empty list is returned.
 fn read_activities(file_path: &Path) -> ActivitiesOrError {
     let contents = match fs::read_to_string(file_path) {
-        Ok(raw) => raw,
+        Ok(raw) => format::upgrade(&raw, FileKind::Activities, file_path)?,
         Err(error) => match error.kind() {
             std::io::ErrorKind::NotFound => return Ok(Vec::new()),
             _ => bail!(error),
@@ -124,16 +359,8 @@ fn read_activities(file_path: &Path) -> ActivitiesOrError {
 
     let mut activities = Vec::new();
 
-    let mut lines = contents.lines();
-    if let Some(preamble) = lines.next() {
-        if preamble.trim() != PREAMBLE_ACTIVITIES_V1 {
-            bail!(ParseError::new(
-                "No valid preamble found - unable to determine file format",
-            ));
-        }
-    } else {
-        bail!(ParseError::new("File seems to be empty"));
-    }
+    // skip the preamble
+    let lines = contents.lines().skip(1);
 
     for line in lines {
         let line = line.trim();
@@ -144,9 +371,9 @@ fn read_activities(file_path: &Path) -> ActivitiesOrError {
         match parse_activity(line) {
             Ok(activity) => activities.push(activity),
             Err(error) => eprintln!(
-                "Skipping unreadable activity <{}> in {}: {:?}",
+                "Skipping unreadable activity <{}> in {} (see 'naday check'): {:?}",
                 line,
-                file_path.to_str().unwrap(),
+                file_path.display(),
                 error
             ),
         }
@@ -155,6 +382,59 @@ fn read_activities(file_path: &Path) -> ActivitiesOrError {
     Ok(activities)
 }
 
+/// Rewrite the given activity file, replacing the first line holding `old` with `new` (or removing
+/// it if `new` is None). All other lines (including comments) are kept as they are. Expects the
+/// caller to hold the lock.
+fn replace_activity(path: &Path, old: &Activity, new: Option<&Activity>) -> Result<()> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Unable to read activities from file {:?}", path))?;
+    let contents = format::upgrade(&contents, FileKind::Activities, path)?;
+
+    let mut found = false;
+    let mut result = String::new();
+
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if !found && !trimmed.starts_with('#') && !trimmed.is_empty() {
+            if let Ok(activity) = parse_activity(trimmed) {
+                if &activity == old {
+                    found = true;
+                    if let Some(new) = new {
+                        result.push_str(&format_activity(new));
+                        result.push('\n');
+                    }
+                    continue;
+                }
+            }
+        }
+
+        result.push_str(line);
+        result.push('\n');
+    }
+
+    if !found {
+        bail!(Error::conflict(format!(
+            "Activity {:?} not found in file {:?}",
+            old, path
+        )));
+    }
+
+    lock::write_atomic(path, &result)
+        .with_context(|| format!("Could not write activities to file {:?}", path))?;
+
+    Ok(())
+}
+
+/// convert an Activity struct into a line of an activity file
+fn format_activity(activity: &Activity) -> String {
+    format!(
+        "{};{};{}",
+        ts2str(activity.timestamp),
+        activity.reps,
+        activity.category
+    )
+}
+
 /// parse a single line from an activity file into an Activity struct
 fn parse_activity(line: &str) -> Result<Activity> {
     let mut parts = line.split(';');
@@ -188,17 +468,30 @@ fn parse_activity(line: &str) -> Result<Activity> {
     })
 }
 
-/// convert activity timestamp to string
+/// convert activity timestamp to string (with UTC offset)
 fn ts2str(timestamp: DateTime<Local>) -> String {
     timestamp.format(ACTIVITY_TS_FORMAT).to_string()
 }
 
-/// parse string as activity timestamp
+/// parse string as activity timestamp. Timestamps without UTC offset are interpreted in the local
+/// time zone.
 fn str2ts<S: AsRef<str>>(raw: S) -> Result<DateTime<Local>> {
-    let ts = Local
-        .datetime_from_str(raw.as_ref().trim(), ACTIVITY_TS_FORMAT)
-        .with_context(|| format!("Unable to patse activity timestamp <{}>", raw.as_ref()))?;
-    Ok(ts)
+    let raw = raw.as_ref().trim();
+    if let Ok(ts) = DateTime::parse_from_str(raw, ACTIVITY_TS_FORMAT) {
+        return Ok(ts.with_timezone(&Local));
+    }
+
+    let naive = NaiveDateTime::parse_from_str(raw, LEGACY_TS_FORMAT)
+        .with_context(|| format!("Unable to parse activity timestamp <{}>", raw))?;
+    match Local.from_local_datetime(&naive) {
+        // ambiguous when the clocks are turned back - the first occurrence is as good as any
+        LocalResult::Single(ts) | LocalResult::Ambiguous(ts, _) => Ok(ts),
+        // skipped when the clocks are turned forward, so it was recorded before the switch
+        LocalResult::None => Local
+            .from_local_datetime(&(naive + Duration::hours(1)))
+            .earliest()
+            .with_context(|| format!("Invalid local activity timestamp <{}>", raw)),
+    }
 }
 
 //
@@ -212,9 +505,13 @@ mod tests {
     use chrono::prelude::{DateTime, Local};
     use chrono::Duration;
     use std::path::Path;
+    use std::process::{Command, Stdio};
+    use std::sync::{Arc, Barrier};
+    use std::thread;
     use tempfile::TempDir;
 
     #[test]
+    #[allow(clippy::manual_main_separator_str)]
     fn store_dirinit() -> Result<()> {
         let tmp_dir = TempDir::new()?;
         let cfg = cfg(&tmp_dir);
@@ -247,9 +544,39 @@ mod tests {
         // Just testing that some cases are parsed at all for now
         super::parse_activity("2003-01-01 00:00:00 ; 1 ; Burpees").unwrap();
         super::parse_activity("2021-12-31 23:59:59 ; 435 ; Plank_Minutes").unwrap();
+
+        let activity = super::parse_activity("2020-12-13 05:43:25+01:00;12;Pushups").unwrap();
+        assert_eq!(
+            Utc.ymd(2020, 12, 13).and_hms(4, 43, 25),
+            activity.timestamp.with_timezone(&Utc)
+        );
+        assert_eq!(
+            activity,
+            super::parse_activity(&format_activity(&activity)).unwrap()
+        );
+    }
+
+    #[test]
+    fn upgrade_v1() {
+        let body = "\
+# Columns: timestamp (local time zone) ; number of repetitions ; category (excercise)
+2020-12-13 05:43:25;12;Pushups
+garbage
+";
+        let upgraded = add_utc_offsets(body).unwrap();
+        let lines: Vec<&str> = upgraded.lines().collect();
+
+        assert_eq!(COLUMNS_COMMENT, lines[0]);
+        assert_eq!(
+            format_activity(&super::parse_activity("2020-12-13 05:43:25;12;Pushups").unwrap()),
+            lines[1]
+        );
+        assert_ne!("2020-12-13 05:43:25;12;Pushups", lines[1]);
+        assert_eq!("garbage", lines[2]);
     }
 
     #[test]
+    #[allow(clippy::bool_comparison)]
     fn init_activity_file() {
         let tmp_dir = TempDir::new().unwrap();
         let cfg = cfg(&tmp_dir);
@@ -263,7 +590,10 @@ mod tests {
         assert!(path.exists());
 
         let contents = fs::read_to_string(path).unwrap();
-        assert_eq!(PREAMBLE_ACTIVITIES_V1, contents.lines().next().unwrap());
+        assert_eq!(
+            FileKind::Activities.preamble(),
+            contents.lines().next().unwrap()
+        );
     }
 
     #[test]
@@ -308,6 +638,90 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn delete_and_update() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let cfg = cfg(&tmp_dir);
+
+        let first = Activity {
+            timestamp: str2ts("2020-12-13 14:34:53")?,
+            reps: 13,
+            category: "Burpees".to_string(),
+        };
+        let second = Activity {
+            timestamp: str2ts("2020-12-14 10:00:00")?,
+            reps: 20,
+            category: "Situps".to_string(),
+        };
+        store(&first, &cfg)?;
+        store(&second, &cfg)?;
+
+        // update within the same file keeps the order
+        let updated = Activity {
+            timestamp: first.timestamp,
+            reps: 14,
+            category: "Burpees".to_string(),
+        };
+        update(&first, &updated, &cfg)?;
+        let path = path_for_date(&first.timestamp.date(), &cfg);
+        let activities = read_activities(&path)?;
+        assert_eq!(vec![updated, second], activities);
+
+        // update into another month moves the activity
+        let moved = Activity {
+            timestamp: str2ts("2021-01-02 10:00:00")?,
+            reps: 20,
+            category: "Situps".to_string(),
+        };
+        update(&activities[1], &moved, &cfg)?;
+        assert_eq!(1, read_activities(&path)?.len());
+        let new_path = path_for_date(&moved.timestamp.date(), &cfg);
+        assert_eq!(vec![moved], read_activities(&new_path)?);
+
+        // delete
+        delete(&activities[0], &cfg)?;
+        assert!(read_activities(&path)?.is_empty());
+        delete(&activities[0], &cfg).expect_err("Deleting twice should fail");
+
+        // comments and preamble survive the rewrites
+        let contents = fs::read_to_string(&path)?;
+        assert_eq!(
+            FileKind::Activities.preamble(),
+            contents.lines().next().unwrap()
+        );
+        assert!(contents.lines().nth(1).unwrap().starts_with('#'));
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_all_files() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let cfg = cfg(&tmp_dir);
+        assert!(read_all(&cfg)?.is_empty());
+
+        for ts in &[
+            "2021-02-01 10:00:00",
+            "2020-12-13 14:34:53",
+            "2021-01-05 08:00:00",
+        ] {
+            let activity = Activity {
+                timestamp: str2ts(ts)?,
+                reps: 10,
+                category: "Pushups".to_string(),
+            };
+            store(&activity, &cfg)?;
+        }
+        fs::write(tmp_dir.path().join("notes.txt"), "not an activity file")?;
+
+        let activities = read_all(&cfg)?;
+        assert_eq!(3, activities.len());
+        assert_eq!(str2ts("2020-12-13 14:34:53")?, activities[0].timestamp);
+        assert_eq!(str2ts("2021-02-01 10:00:00")?, activities[2].timestamp);
+
+        Ok(())
+    }
+
     #[test]
     fn multiple_months() {
         let tmp_dir = TempDir::new().unwrap();
@@ -351,9 +765,177 @@ mod tests {
         }
     }
 
-    fn cfg(tmp: &TempDir) -> Config {
-        Config {
-            data_dir: tmp.path().to_str().unwrap().to_string(),
+    #[test]
+    fn concurrent_writers() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let cfg = cfg(&tmp_dir);
+        let day = Local.ymd(2021, 5, 3);
+
+        // some activities to delete while the others are being written
+        let doomed: Vec<Activity> = (0..10)
+            .map(|idx| activity_at(day.and_hms(6, 0, idx), 1))
+            .collect();
+        for activity in &doomed {
+            store(activity, &cfg)?;
+        }
+        let path = path_for_date(&day, &cfg);
+
+        let barrier = Arc::new(Barrier::new(9));
+        let mut handles = Vec::new();
+        for writer in 0..8 {
+            let (cfg, barrier) = (cfg.clone(), barrier.clone());
+            handles.push(thread::spawn(move || {
+                barrier.wait();
+                for idx in 0..25 {
+                    let timestamp = day.and_hms(12, writer, idx);
+                    store(&activity_at(timestamp, writer + 2), &cfg).unwrap();
+                }
+            }));
+        }
+        let (delete_cfg, delete_barrier) = (cfg.clone(), barrier.clone());
+        handles.push(thread::spawn(move || {
+            delete_barrier.wait();
+            for activity in &doomed {
+                delete(activity, &delete_cfg).unwrap();
+            }
+        }));
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let contents = fs::read_to_string(&path)?;
+        assert_eq!(
+            1,
+            contents.matches(&FileKind::Activities.preamble()).count()
+        );
+        let activities = read_activities(&path)?;
+        assert_eq!(8 * 25, activities.len());
+        assert!(activities.iter().all(|activity| activity.reps > 1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_processes() -> Result<()> {
+        let day = Local.ymd(2021, 5, 3);
+
+        // re-executed by the test below: act as one of the writer processes
+        if let Ok(data_dir) = std::env::var(WRITER_DIR_VAR) {
+            let cfg = Config::new(&data_dir);
+            let writer: u32 = std::env::var(WRITER_ID_VAR)?.parse()?;
+            for idx in 0..50 {
+                store(&activity_at(day.and_hms(12, writer, idx), writer + 1), &cfg)?;
+            }
+            return Ok(());
+        }
+
+        let tmp_dir = TempDir::new()?;
+        let cfg = cfg(&tmp_dir);
+        let children = (0..2)
+            .map(|writer| {
+                Command::new(std::env::current_exe()?)
+                    .args([
+                        "--exact",
+                        "storage::fs::activity::tests::concurrent_processes",
+                    ])
+                    .env(WRITER_DIR_VAR, &cfg.data_dir)
+                    .env(WRITER_ID_VAR, writer.to_string())
+                    .stdout(Stdio::null())
+                    .spawn()
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        for mut child in children {
+            assert!(child.wait()?.success());
+        }
+
+        let path = path_for_date(&day, &cfg);
+        let contents = fs::read_to_string(&path)?;
+        assert_eq!(
+            1,
+            contents.matches(&FileKind::Activities.preamble()).count()
+        );
+        assert_eq!(2 * 50, read_activities(&path)?.len());
+
+        Ok(())
+    }
+
+    const WRITER_DIR_VAR: &str = "NADAY_TEST_WRITER_DIR";
+    const WRITER_ID_VAR: &str = "NADAY_TEST_WRITER_ID";
+
+    #[test]
+    fn check_and_fix_files() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let cfg = cfg(&tmp_dir);
+        let march = tmp_dir.path().join("2021-03.txt");
+        fs::write(
+            &march,
+            "naday activities v1\n\
+             # comment\n\
+             2021-03-01 10:00:00;10;Pushups\n\
+             garbage\n\
+             2021-04-02 10:00:00;12;Pushups\n",
+        )?;
+        fs::write(tmp_dir.path().join("2021-05.txt"), "no preamble\n")?;
+
+        let findings = check_files(&cfg, false)?;
+        let problems: Vec<String> = findings.iter().map(|f| f.to_string()).collect();
+        assert_eq!(3, findings.len(), "{:?}", problems);
+        assert!(matches!(
+            findings[0].problem,
+            Problem::MalformedLine { line: 4, .. }
+        ));
+        assert!(matches!(
+            findings[1].problem,
+            Problem::WrongFile { line: 5, .. }
+        ));
+        assert!(matches!(
+            findings[2].problem,
+            Problem::UnreadableFile { .. }
+        ));
+
+        let fixed: Vec<bool> = check_files(&cfg, true)?.iter().map(|f| f.fixed).collect();
+        assert_eq!(vec![true, true, false], fixed);
+        assert_eq!(1, check_files(&cfg, false)?.len());
+
+        let contents = fs::read_to_string(&march)?;
+        assert!(contents.contains("# unreadable: garbage\n"));
+        assert_eq!(1, read_activities(&march)?.len());
+        let april = read_activities(&tmp_dir.path().join("2021-04.txt"))?;
+        assert_eq!(12, april[0].reps);
+
+        Ok(())
+    }
+
+    #[test]
+    fn day_rollover() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let mut cfg = cfg(&tmp_dir);
+        cfg.day_starts_at = 4;
+
+        // a late session on January 31st
+        let late = activity_at(Local.ymd(2021, 2, 1).and_hms(0, 30, 0), 10);
+        let early = activity_at(Local.ymd(2021, 2, 1).and_hms(4, 0, 0), 20);
+        store(&late, &cfg)?;
+        store(&early, &cfg)?;
+
+        assert!(tmp_dir.path().join("2021-01.txt").exists());
+        let jan31 = Local.ymd(2021, 1, 31);
+        assert_eq!(vec![late], read_days(&jan31, &jan31, &cfg)?);
+        assert_eq!(vec![early], read_days(&jan31.succ(), &jan31.succ(), &cfg)?);
+        assert!(check_files(&cfg, false)?.is_empty());
+
+        Ok(())
+    }
+
+    fn activity_at(timestamp: DateTime<Local>, reps: u32) -> Activity {
+        Activity {
+            timestamp,
+            reps,
+            category: "Pushups".to_string(),
         }
     }
+
+    fn cfg(tmp: &TempDir) -> Config {
+        Config::new(tmp.path().to_str().unwrap())
+    }
 }
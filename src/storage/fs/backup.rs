@@ -0,0 +1,257 @@
+use crate::model::Config;
+
+use super::lock;
+use crate::error::Error;
+use anyhow::{bail, Context, Result};
+use chrono::{Local, NaiveDateTime};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A snapshot of all files in the data directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backup {
+    /// Unique id, also the name of the backup directory: '<YYYYmmdd-HHMMSS>-<reason>'
+    pub id: String,
+    pub created: NaiveDateTime,
+    /// Why the backup was created, e.g. 'manual', 'daily' or the name of a rewriting operation
+    pub reason: String,
+    /// Number of files in the backup
+    pub files: usize,
+}
+
+/// Reason of backups created on request. These are not deleted automatically.
+pub const MANUAL: &str = "manual";
+
+/// Create a backup of the data directory. Unless it is a manual backup, the oldest automatic
+/// backups are deleted afterwards.
+pub fn create(cfg: &Config, reason: &str) -> Result<Backup> {
+    let _lock = lock::lock(cfg)?;
+    create_locked(cfg, reason)
+}
+
+/// Like `create()`, but expects the caller to hold the lock
+pub fn create_locked(cfg: &Config, reason: &str) -> Result<Backup> {
+    let data_dir = super::init_data_dir(cfg)?;
+    let created = Local::now().naive_local();
+    let id = format!("{}-{}", created.format(ID_FORMAT), reason);
+    let dir = data_dir.join(BACKUP_DIR).join(&id);
+
+    // several backups for the same reason within a second are the same
+    if !dir.exists() {
+        let tmp_dir = dir.with_extension("tmp");
+        fs::create_dir_all(&tmp_dir)
+            .with_context(|| format!("Could not create backup directory {:?}", &tmp_dir))?;
+        for path in data_files(&data_dir)? {
+            let target = tmp_dir.join(path.file_name().unwrap());
+            fs::copy(&path, &target)
+                .with_context(|| format!("Could not copy {:?} to {:?}", &path, &target))?;
+        }
+        fs::rename(&tmp_dir, &dir)
+            .with_context(|| format!("Could not create backup directory {:?}", &dir))?;
+    }
+
+    if reason != MANUAL {
+        rotate(cfg)?;
+    }
+
+    read_backup(&dir)?.context("Backup vanished after creating it")
+}
+
+/// Create the daily backup, unless there already is one from today
+pub fn create_daily(cfg: &Config) -> Result<Option<Backup>> {
+    let today = Local::today().naive_local();
+    let exists = list(cfg)?
+        .iter()
+        .any(|backup| backup.reason == DAILY && backup.created.date() == today);
+    if exists {
+        return Ok(None);
+    }
+
+    create(cfg, DAILY).map(Some)
+}
+
+/// All backups, sorted by creation time
+pub fn list(cfg: &Config) -> Result<Vec<Backup>> {
+    let dir = Path::new(&cfg.data_dir).join(BACKUP_DIR);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => bail!(error),
+    };
+
+    let mut backups = Vec::new();
+    for entry in entries {
+        if let Some(backup) = read_backup(&entry?.path())? {
+            backups.push(backup);
+        }
+    }
+
+    backups.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(backups)
+}
+
+/// Replace all files in the data directory with the ones from the given backup. The current
+/// state is backed up before.
+///
+/// # Returns
+/// The backup of the state before restoring
+pub fn restore(cfg: &Config, id: &str) -> Result<Backup> {
+    let _lock = lock::lock(cfg)?;
+    let data_dir = super::init_data_dir(cfg)?;
+
+    let source = match list(cfg)?.into_iter().find(|backup| backup.id == id) {
+        Some(backup) => data_dir.join(BACKUP_DIR).join(&backup.id),
+        None => bail!(Error::validation(format!("There is no backup '{}'", id))),
+    };
+    // read everything before backing up, as rotating might delete the source
+    let mut files = Vec::new();
+    for path in data_files(&source)? {
+        let contents =
+            fs::read(&path).with_context(|| format!("Could not read backup file {:?}", &path))?;
+        files.push((data_dir.join(path.file_name().unwrap()), contents));
+    }
+
+    let before = create_locked(cfg, "restore")?;
+
+    for path in data_files(&data_dir)? {
+        fs::remove_file(&path).with_context(|| format!("Could not remove {:?}", &path))?;
+    }
+    for (target, contents) in files {
+        lock::write_atomic(&target, &contents)
+            .with_context(|| format!("Could not restore {:?}", &target))?;
+    }
+
+    Ok(before)
+}
+
+//
+// Internals ---------------------------
+//
+
+/// Directory (inside the data directory) holding all backups
+const BACKUP_DIR: &str = "backups";
+const ID_FORMAT: &str = "%Y%m%d-%H%M%S";
+const DAILY: &str = "daily";
+
+/// All regular files directly in the given directory, except lock and temporary files
+fn data_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+
+    for entry in fs::read_dir(dir).with_context(|| format!("Could not list {:?}", dir))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_file() && !name.starts_with('.') && !name.ends_with(".tmp") {
+            paths.push(entry.path());
+        }
+    }
+
+    paths.sort();
+    Ok(paths)
+}
+
+/// Read the metadata of the backup in the given directory. Returns None if it is not a backup.
+fn read_backup(dir: &Path) -> Result<Option<Backup>> {
+    let id = match dir.file_name().and_then(|name| name.to_str()) {
+        Some(id) if dir.is_dir() && !id.ends_with(".tmp") => id.to_string(),
+        _ => return Ok(None),
+    };
+
+    // '<date>-<time>-<reason>'
+    let mut parts = id.splitn(3, '-');
+    let (date, time, reason) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(date), Some(time), Some(reason)) => (date, time, reason),
+        _ => return Ok(None),
+    };
+    let created = match NaiveDateTime::parse_from_str(&format!("{}-{}", date, time), ID_FORMAT) {
+        Ok(created) => created,
+        Err(_) => return Ok(None),
+    };
+
+    Ok(Some(Backup {
+        reason: reason.to_string(),
+        created,
+        files: data_files(dir)?.len(),
+        id,
+    }))
+}
+
+/// Delete the oldest automatic backups, keeping the configured number
+fn rotate(cfg: &Config) -> Result<()> {
+    let automatic: Vec<Backup> = list(cfg)?
+        .into_iter()
+        .filter(|backup| backup.reason != MANUAL)
+        .collect();
+
+    let excess = automatic.len().saturating_sub(cfg.backups_to_keep);
+    for backup in &automatic[..excess] {
+        let dir = Path::new(&cfg.data_dir).join(BACKUP_DIR).join(&backup.id);
+        fs::remove_dir_all(&dir).with_context(|| format!("Could not delete backup {:?}", &dir))?;
+    }
+
+    Ok(())
+}
+
+//
+// Tests ---------------------------------
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn create_and_restore() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cfg = Config::new(tmp_dir.path().to_str().unwrap());
+        let dir = tmp_dir.path();
+        fs::write(dir.join("2021-01.txt"), "old").unwrap();
+        fs::write(dir.join("categories.txt"), "categories").unwrap();
+
+        let backup = create(&cfg, MANUAL).unwrap();
+        assert_eq!(MANUAL, backup.reason);
+        assert_eq!(2, backup.files);
+        assert_eq!(vec![backup.clone()], list(&cfg).unwrap());
+
+        fs::write(dir.join("2021-01.txt"), "new").unwrap();
+        fs::write(dir.join("2021-02.txt"), "new").unwrap();
+        let before = restore(&cfg, &backup.id).unwrap();
+        assert_eq!("restore", before.reason);
+        assert_eq!(3, before.files);
+
+        assert_eq!("old", fs::read_to_string(dir.join("2021-01.txt")).unwrap());
+        assert!(!dir.join("2021-02.txt").exists());
+
+        let error: Error = restore(&cfg, "20000101-000000-manual").unwrap_err().into();
+        assert_eq!(7, error.exit_code());
+    }
+
+    #[test]
+    fn rotation() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut cfg = Config::new(tmp_dir.path().to_str().unwrap());
+        cfg.backups_to_keep = 2;
+        fs::write(tmp_dir.path().join("2021-01.txt"), "data").unwrap();
+
+        let backups_dir = tmp_dir.path().join(BACKUP_DIR);
+        for id in &[
+            "20200101-100000-check",
+            "20200102-100000-manual",
+            "20200103-100000-daily",
+        ] {
+            fs::create_dir_all(backups_dir.join(id)).unwrap();
+        }
+        create(&cfg, "migrate").unwrap();
+
+        let reasons = |cfg: &Config| -> Vec<String> {
+            list(cfg).unwrap().into_iter().map(|b| b.reason).collect()
+        };
+        assert_eq!(vec!["manual", "daily", "migrate"], reasons(&cfg));
+
+        // only one daily backup per day
+        assert!(create_daily(&cfg).unwrap().is_some());
+        assert!(create_daily(&cfg).unwrap().is_none());
+        let mut remaining = reasons(&cfg);
+        remaining.sort();
+        assert_eq!(vec!["daily", "manual", "migrate"], remaining);
+    }
+}
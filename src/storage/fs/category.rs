@@ -1,31 +1,116 @@
-use crate::model::{Category, Config};
+use crate::model::{Category, CategoryLookup, Config};
 
-use crate::error::ParseError;
+use super::format::{self, FileKind};
+use super::lock;
+use crate::check::{Finding, Problem};
+use crate::error::Error;
 use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+use std::collections::HashSet;
 use std::fs;
 use std::fs::{File, OpenOptions};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub fn read_categories(cfg: &Config) -> Result<Vec<Category>> {
-    let path = &(init_category_file(cfg)?);
-    let contents = fs::read_to_string(path)
-        .with_context(|| format!("Unable to read category file {:?}", path))?;
+    read_category_file(&init_category_file(cfg)?)
+}
 
-    let mut categories: Vec<Category> = Vec::new();
-    let mut lines = contents.lines();
+/// Read all categories without creating the category file: Without one, the defaults apply.
+pub fn read_categories_read_only(cfg: &Config) -> Result<Vec<Category>> {
+    let path = Path::new(&cfg.data_dir).join(CATEGORY_FILE);
+    if path.exists() {
+        read_category_file(&path)
+    } else {
+        Ok(default_categories())
+    }
+}
 
-    if let Some(preamble) = lines.next() {
-        if preamble.trim() != PREAMBLE_CATEGORIES_V1 {
-            bail!(ParseError::new(
-                "No valid preamble found - unable to determine category file format",
-            ));
+/// Merge a (sync) conflict copy into the category file: All categories of the copy that are not
+/// known yet are appended, unless their names or aliases collide with known ones. Expects the
+/// caller to hold the lock.
+///
+/// # Returns
+/// The number of merged categories, plus notes on categories that could not be merged
+pub fn merge_file(cfg: &Config, copy: &Path) -> Result<(usize, Vec<String>)> {
+    let path = super::init_data_dir(cfg)?.join(CATEGORY_FILE);
+    if !path.exists() {
+        fs::copy(copy, &path)
+            .with_context(|| format!("Could not copy {:?} to {:?}", copy, &path))?;
+        return Ok((read_category_file(&path)?.len(), Vec::new()));
+    }
+
+    let known = read_category_file(&path)?;
+    let theirs = read_category_file(copy)?;
+
+    let mut lookup = CategoryLookup::new();
+    for category in &known {
+        lookup.add(category.clone())?;
+    }
+
+    let mut merged = Vec::new();
+    let mut notes = Vec::new();
+    for category in theirs {
+        match lookup.get(&category.name) {
+            Some(existing) => {
+                if format_category(&existing) != format_category(&category) {
+                    notes.push(format!(
+                        "Category {} is defined differently, keeping <{}> instead of <{}>",
+                        &category.name,
+                        format_category(&existing),
+                        format_category(&category)
+                    ));
+                }
+            }
+            None => match lookup.add(category.clone()) {
+                Ok(()) => merged.push(category),
+                Err(error) => notes.push(format!(
+                    "Category {} was not merged: {}",
+                    &category.name, error
+                )),
+            },
         }
-    } else {
-        bail!(ParseError::new("Category file seems to be empty"));
     }
 
-    for line in lines {
+    if !merged.is_empty() {
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Unable to read category file {:?}", &path))?;
+        let mut contents = format::upgrade(&contents, FileKind::Categories, &path)?;
+        if !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        for category in &merged {
+            contents.push_str(&format_category(category));
+            contents.push('\n');
+        }
+
+        lock::write_atomic(&path, &contents)
+            .with_context(|| format!("Could not write categories to file {:?}", &path))?;
+    }
+
+    Ok((merged.len(), notes))
+}
+
+/// Format upgrade v1 -> v2: Categories can have a daily goal. The existing lines stay valid.
+pub fn allow_goals(body: &str) -> Result<String> {
+    Ok(body.to_string())
+}
+
+/// Whether the given file name is the name of the category file
+pub fn is_category_file(file_name: &str) -> bool {
+    file_name == CATEGORY_FILE
+}
+
+/// Read all categories in the given category file
+pub fn read_category_file(path: &Path) -> Result<Vec<Category>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Unable to read category file {:?}", path))?;
+    let contents = format::upgrade(&contents, FileKind::Categories, path)?;
+
+    let mut categories: Vec<Category> = Vec::new();
+
+    // skip the preamble
+    for line in contents.lines().skip(1) {
         let line = line.trim();
         if line.starts_with('#') || line.is_empty() {
             continue;
@@ -36,7 +121,7 @@ pub fn read_categories(cfg: &Config) -> Result<Vec<Category>> {
             Err(msg) => eprintln!(
                 "Skipping unreadable category <{}> in {}: {}",
                 line,
-                path.to_str().unwrap(),
+                path.display(),
                 msg
             ),
         }
@@ -45,36 +130,135 @@ pub fn read_categories(cfg: &Config) -> Result<Vec<Category>> {
     Ok(categories)
 }
 
+/// Scan the category file for lines that are skipped when reading it: Unreadable lines and
+/// categories defined more than once. None of these can be repaired automatically.
+pub fn check_file(cfg: &Config) -> Result<Vec<Finding>> {
+    let path = Path::new(&cfg.data_dir).join(CATEGORY_FILE);
+    let file = path.display().to_string();
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => {
+            return Err(error).with_context(|| format!("Unable to read category file {:?}", &path))
+        }
+    };
+    let contents = match format::upgrade(&contents, FileKind::Categories, &path) {
+        Ok(contents) => contents,
+        Err(error) => {
+            let msg = match Error::from(error) {
+                Error::Parse { msg, .. } => msg,
+                error => error.to_string(),
+            };
+            return Ok(vec![Finding::new(
+                Problem::UnreadableFile { file, msg },
+                false,
+            )]);
+        }
+    };
+
+    let mut findings = Vec::new();
+    let mut names = HashSet::new();
+    // the preamble is line 1
+    for (line_no, line) in contents.lines().enumerate().skip(1) {
+        let line = line.trim();
+        if line.starts_with('#') || line.is_empty() {
+            continue;
+        }
+
+        let problem = match parse_category(line) {
+            Err(_) => Problem::MalformedLine {
+                file: file.clone(),
+                line: line_no + 1,
+                content: line.to_string(),
+            },
+            Ok(category) if !names.insert(category.name.to_lowercase()) => {
+                Problem::DuplicateCategory {
+                    file: file.clone(),
+                    line: line_no + 1,
+                    name: category.name,
+                }
+            }
+            Ok(_) => continue,
+        };
+        findings.push(Finding::new(problem, false));
+    }
+
+    Ok(findings)
+}
+
+/// Append the given category to the category file
+pub fn append_category(category: &Category, cfg: &Config) -> Result<()> {
+    let path = init_category_file(cfg)?;
+
+    let _lock = lock::lock(cfg)?;
+    let contents = fs::read_to_string(&path)
+        .with_context(|| format!("Unable to read category file {:?}", &path))?;
+
+    let mut file: File = OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Could not open category file {:?} for writing", &path))?;
+
+    // don't glue the new category to a manually edited last line without line break
+    let separator = if contents.ends_with('\n') { "" } else { "\n" };
+
+    writeln!(&mut file, "{}{}", separator, format_category(category))
+        .with_context(|| format!("Could not write category to file {:?}", &path))?;
+
+    Ok(())
+}
+
+/// Replace the contents of the category file with the given categories
+pub fn write_categories(categories: &[Category], cfg: &Config) -> Result<()> {
+    let path = init_category_file(cfg)?;
+
+    let mut contents = format!(
+        "{}\n{}",
+        FileKind::Categories.preamble(),
+        CATEGORY_FILE_HEADER
+    );
+    for category in categories {
+        contents.push_str(&format_category(category));
+        contents.push('\n');
+    }
+
+    let _lock = lock::lock(cfg)?;
+    lock::write_atomic(&path, &contents)
+        .with_context(|| format!("Could not write categories to file {:?}", &path))?;
+
+    Ok(())
+}
+
+/// The categories a new category file is initialized with
+pub fn default_categories() -> Vec<Category> {
+    DEFAULT_CATEGORIES
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| parse_category(line).unwrap())
+        .collect()
+}
+
 //
 // Internals ---------------------------
 //
 
-const PREAMBLE_CATEGORIES_V1: &str = "naday categories v1";
-
-fn init_category_file(cfg: &Config) -> Result<PathBuf> {
-    let mut path = super::init_data_dir(cfg)?;
-
-    path.push("categories.txt");
+pub const CATEGORY_FILE: &str = "categories.txt";
+const SINCE_FORMAT: &str = "%Y-%m-%d";
 
-    if !path.exists() {
-        let mut file: File = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open(&path)
-            .with_context(|| {
-                format!(
-                    "Could not open category file <{}> for writing",
-                    path.display()
-                )
-            })?;
-
-        writeln!(&mut file, "\
-{}
+const CATEGORY_FILE_HEADER: &str = "\
 # List of activity categories and their attributes for the 'naday' tool (https://github.com/creinig/naday).
 # Lines beginning with '#' are comments and are ignored by the tool.
 # The remaining lines are basically plain CSV, with one category per line.
 # Separator character is ';', encoding is UTF-8.
 # Columns: display name ; 'weight' of repetitions in relation to other activities [; alias]*
+# A weight change is recorded by appending 'since=<YYYY-MM-DD>;<new weight>' - the new weight is
+# applied to all activities from that day on, e.g. 'Burpees;1.5;bu;since=2021-01-01;1.2'.
+# Categories with an additional column 'archived=true' can't be used for logging anymore, but
+# their weight is still applied in reports.
+# A daily goal is set with an additional column 'goal=<repetitions>', e.g. 'Pushups;1;pu;goal=100'.
+";
+
+const DEFAULT_CATEGORIES: &str = "\
 Pushups;1;pu;push
 Situps;1;si
 Burpees;1.5;bu
@@ -82,13 +266,45 @@ PlankSeconds;0.33;pl
 WalkingSteps;0.01;wa
 # General category for unplanned / one-off strenuous activity
 Extra;1;x
-", PREAMBLE_CATEGORIES_V1)?;
-    }
+";
+
+fn init_category_file(cfg: &Config) -> Result<PathBuf> {
+    let mut path = super::init_data_dir(cfg)?;
+
+    path.push(CATEGORY_FILE);
+
+    let contents = format!(
+        "{}\n{}{}",
+        FileKind::Categories.preamble(),
+        CATEGORY_FILE_HEADER,
+        DEFAULT_CATEGORIES
+    );
+    lock::init_file(&path, &contents, cfg)
+        .with_context(|| format!("Could not initialize category file {:?}", &path))?;
 
-    // return a readonly handle
     Ok(path)
 }
 
+/// Convert a category into a line of the category file
+fn format_category(category: &Category) -> String {
+    let mut line = format!("{};{}", category.name, category.weight);
+    for alias in &category.aliases {
+        line.push(';');
+        line.push_str(alias);
+    }
+    for (since, weight) in &category.weight_changes {
+        line.push_str(&format!(";since={};{}", since.format(SINCE_FORMAT), weight));
+    }
+    if let Some(goal) = category.goal {
+        line.push_str(&format!(";goal={}", goal));
+    }
+    if category.archived {
+        line.push_str(";archived=true");
+    }
+
+    line
+}
+
 fn parse_category(line: &str) -> Result<Category> {
     let mut parts = line.split(';');
 
@@ -108,11 +324,58 @@ fn parse_category(line: &str) -> Result<Category> {
     };
 
     let mut aliases = Vec::new();
-    for alias in parts {
-        aliases.push(alias.trim());
+    let mut archived = false;
+    let mut goal = None;
+    let mut weight_changes = Vec::new();
+    while let Some(part) = parts.next() {
+        match part.split_once('=') {
+            Some((key, value)) => match key.trim() {
+                "archived" => {
+                    archived = value.trim().parse().with_context(|| {
+                        format!(
+                            "Unable to parse archived flag <{}> in line <{}>",
+                            value, line
+                        )
+                    })?
+                }
+                "goal" => {
+                    goal = Some(value.trim().parse().with_context(|| {
+                        format!("Unable to parse goal <{}> in line <{}>", value, line)
+                    })?)
+                }
+                "since" => {
+                    let since = NaiveDate::parse_from_str(value.trim(), SINCE_FORMAT)
+                        .with_context(|| {
+                            format!(
+                                "Unable to parse weight change date <{}> in line <{}>",
+                                value, line
+                            )
+                        })?;
+                    let weight: f64 = match parts.next() {
+                        Some(weight) => weight.trim().parse().with_context(|| {
+                            format!(
+                                "Unable to parse category weight <{}> in line <{}>",
+                                weight, line
+                            )
+                        })?,
+                        None => bail!("No weight found after <{}> in line <{}>", part, line),
+                    };
+                    weight_changes.push((since, weight));
+                }
+                _ => bail!("Unknown category attribute <{}> in line <{}>", key, line),
+            },
+            None => aliases.push(part.trim()),
+        }
+    }
+
+    let mut category = Category::new(name, weight, aliases);
+    category.archived = archived;
+    category.goal = goal;
+    for (since, weight) in weight_changes {
+        category.change_weight(since, weight);
     }
 
-    Ok(Category::new(name, weight, aliases))
+    Ok(category)
 }
 
 //
@@ -125,6 +388,7 @@ mod test {
     use tempfile::TempDir;
 
     #[test]
+    #[allow(clippy::get_first)]
     fn file_init() {
         let tmp_dir = TempDir::new().unwrap();
         let cfg = cfg(&tmp_dir);
@@ -152,9 +416,98 @@ mod test {
         );
     }
 
+    #[test]
+    fn archived() {
+        let category = parse_category("Lunges;0.5;lu;archived=true").unwrap();
+        assert_eq!(
+            "Category (Lunges, 0.5, [\"lu\"], archived)",
+            category.to_string()
+        );
+        assert_eq!("Lunges;0.5;lu;archived=true", format_category(&category));
+
+        let category = parse_category("Lunges;0.5;archived=false;lu").unwrap();
+        assert_eq!("Category (Lunges, 0.5, [\"lu\"])", category.to_string());
+
+        let category = parse_category("Lunges;0.5;goal=50;lu;archived=true").unwrap();
+        assert_eq!(Some(50), category.goal);
+        assert_eq!(
+            "Lunges;0.5;lu;goal=50;archived=true",
+            format_category(&category)
+        );
+
+        parse_category("Lunges;0.5;goal=lots").unwrap_err();
+        parse_category("Lunges;0.5;archived=maybe").unwrap_err();
+        parse_category("Lunges;0.5;color=red").unwrap_err();
+    }
+
+    #[test]
+    fn weight_changes() {
+        let category = parse_category("Burpees;1.5;bu;since=2021-01-01;1.2").unwrap();
+        assert_eq!(
+            "Category (Burpees, 1.5, [\"bu\"], 1.2 since 2021-01-01)",
+            category.to_string()
+        );
+        assert_eq!(
+            "Burpees;1.5;bu;since=2021-01-01;1.2",
+            format_category(&category)
+        );
+
+        let category =
+            parse_category("Burpees;1.5;since=2022-01-01;2;since=2021-01-01;1.2;bu").unwrap();
+        assert_eq!(
+            "Burpees;1.5;bu;since=2021-01-01;1.2;since=2022-01-01;2",
+            format_category(&category)
+        );
+
+        parse_category("Burpees;1.5;since=2021-01-01").unwrap_err();
+        parse_category("Burpees;1.5;since=2021-01-01;bu").unwrap_err();
+        parse_category("Burpees;1.5;since=yesterday;1.2").unwrap_err();
+    }
+
+    #[test]
+    fn write() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cfg = cfg(&tmp_dir);
+
+        let mut lunges = Category::new("Lunges", 1.0, vec!["lu"]);
+        lunges.archived = true;
+        write_categories(&[Category::new("Squats", 1.2, vec!["sq"]), lunges], &cfg).unwrap();
+
+        let categories = read_categories(&cfg).unwrap();
+        assert_eq!(2, categories.len());
+        assert_eq!(
+            "Category (Squats, 1.2, [\"sq\"])",
+            categories.first().unwrap().to_string()
+        );
+        assert_eq!(
+            "Category (Lunges, 1, [\"lu\"], archived)",
+            categories.get(1).unwrap().to_string()
+        );
+
+        assert_eq!(6, default_categories().len());
+    }
+
+    #[test]
+    fn append() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cfg = cfg(&tmp_dir);
+
+        append_category(&Category::new("Lunges", 1.0, vec!["lu"]), &cfg).unwrap();
+        append_category(&Category::new("Squats", 1.2, Vec::<String>::new()), &cfg).unwrap();
+
+        let categories = read_categories(&cfg).unwrap();
+        assert_eq!(8, categories.len());
+        assert_eq!(
+            "Category (Lunges, 1, [\"lu\"])",
+            categories.get(6).unwrap().to_string()
+        );
+        assert_eq!(
+            "Category (Squats, 1.2, [])",
+            categories.get(7).unwrap().to_string()
+        );
+    }
+
     fn cfg(tmp: &TempDir) -> Config {
-        Config {
-            data_dir: tmp.path().to_str().unwrap().to_string(),
-        }
+        Config::new(tmp.path().to_str().unwrap())
     }
 }
@@ -0,0 +1,213 @@
+use crate::model::{Challenge, ChallengeTarget, Config};
+
+use super::format::{self, FileKind};
+use super::lock;
+use crate::error::Error;
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Read all challenges from the challenge file
+pub fn read_challenges(cfg: &Config) -> Result<Vec<Challenge>> {
+    let path = init_challenge_file(cfg)?;
+    let contents = fs::read_to_string(&path)
+        .with_context(|| format!("Unable to read challenge file {:?}", &path))?;
+    let contents = format::upgrade(&contents, FileKind::Challenges, &path)?;
+
+    let mut challenges = Vec::new();
+    // the preamble is line 1
+    let lines = contents.lines().skip(1);
+    for (line_no, line) in lines.enumerate().map(|(idx, line)| (idx + 2, line)) {
+        let line = line.trim();
+        if line.starts_with('#') || line.is_empty() {
+            continue;
+        }
+
+        match parse_challenge(line) {
+            Ok(challenge) => challenges.push(challenge),
+            Err(error) => bail!(Error::parse(
+                path.display().to_string(),
+                Some(line_no),
+                format!("Invalid challenge <{}>: {:#}", line, error)
+            )),
+        }
+    }
+
+    Ok(challenges)
+}
+
+/// Append the given challenge to the challenge file
+pub fn append_challenge(challenge: &Challenge, cfg: &Config) -> Result<()> {
+    let path = init_challenge_file(cfg)?;
+
+    let _lock = lock::lock(cfg)?;
+    let contents = fs::read_to_string(&path)
+        .with_context(|| format!("Unable to read challenge file {:?}", &path))?;
+
+    let mut file: File = OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Could not open challenge file {:?} for writing", &path))?;
+
+    // don't glue the new challenge to a manually edited last line without line break
+    let separator = if contents.ends_with('\n') { "" } else { "\n" };
+
+    writeln!(&mut file, "{}{}", separator, format_challenge(challenge))
+        .with_context(|| format!("Could not write challenge to file {:?}", &path))?;
+
+    Ok(())
+}
+
+//
+// Internals ---------------------------
+//
+
+pub const CHALLENGE_FILE: &str = "challenges.txt";
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+const CHALLENGE_FILE_HEADER: &str = "\
+# List of challenges for the 'naday' tool (https://github.com/creinig/naday).
+# Lines beginning with '#' are comments and are ignored by the tool.
+# The remaining lines are basically plain CSV, with one challenge per line.
+# Separator character is ';', encoding is UTF-8.
+# Columns: name ; category ; first day (YYYY-MM-DD) ; last day ; target
+# The target is either 'total=<repetitions>' for the whole challenge or 'daily=<repetitions>'
+# for each day, e.g. 'october;Pushups;2026-10-01;2026-10-31;total=3000'.
+";
+
+fn init_challenge_file(cfg: &Config) -> Result<PathBuf> {
+    let mut path = super::init_data_dir(cfg)?;
+
+    path.push(CHALLENGE_FILE);
+
+    let contents = format!(
+        "{}\n{}",
+        FileKind::Challenges.preamble(),
+        CHALLENGE_FILE_HEADER
+    );
+    lock::init_file(&path, &contents, cfg)
+        .with_context(|| format!("Could not initialize challenge file {:?}", &path))?;
+
+    Ok(path)
+}
+
+/// Convert a challenge into a line of the challenge file
+fn format_challenge(challenge: &Challenge) -> String {
+    let target = match challenge.target {
+        ChallengeTarget::Total(total) => format!("total={}", total),
+        ChallengeTarget::Daily(daily) => format!("daily={}", daily),
+    };
+
+    format!(
+        "{};{};{};{};{}",
+        challenge.name,
+        challenge.category,
+        challenge.start.format(DATE_FORMAT),
+        challenge.end.format(DATE_FORMAT),
+        target
+    )
+}
+
+fn parse_challenge(line: &str) -> Result<Challenge> {
+    let parts: Vec<&str> = line.split(';').map(|part| part.trim()).collect();
+    let (name, category, start, end, target) = match parts.as_slice() {
+        [name, category, start, end, target] => (*name, *category, *start, *end, *target),
+        _ => bail!("Expected 5 columns: name;category;first day;last day;target"),
+    };
+
+    let parse_date = |date: &str| {
+        NaiveDate::parse_from_str(date, DATE_FORMAT)
+            .with_context(|| format!("Unable to parse date <{}>", date))
+    };
+    let start = parse_date(start)?;
+    let end = parse_date(end)?;
+    if end < start {
+        bail!("The last day is before the first day");
+    }
+
+    let target = match target.split_once('=') {
+        Some((kind, reps)) => {
+            let reps: u32 = reps
+                .trim()
+                .parse()
+                .with_context(|| format!("Unable to parse repetitions <{}>", reps))?;
+            match kind.trim() {
+                "total" => ChallengeTarget::Total(reps),
+                "daily" => ChallengeTarget::Daily(reps),
+                _ => bail!("Unknown target <{}>, expected 'total' or 'daily'", kind),
+            }
+        }
+        None => bail!("Invalid target <{}>, expected e.g. 'total=3000'", target),
+    };
+
+    Ok(Challenge {
+        name: name.to_string(),
+        category: category.to_string(),
+        start,
+        end,
+        target,
+    })
+}
+
+//
+// Tests --------------------------------
+//
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn read_and_append() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cfg = Config::new(tmp_dir.path().to_str().unwrap());
+        assert!(read_challenges(&cfg).unwrap().is_empty());
+
+        let challenge = Challenge {
+            name: "october".to_string(),
+            category: "Pushups".to_string(),
+            start: NaiveDate::from_ymd(2026, 10, 1),
+            end: NaiveDate::from_ymd(2026, 10, 31),
+            target: ChallengeTarget::Total(3000),
+        };
+        append_challenge(&challenge, &cfg).unwrap();
+        let burpees = parse_challenge("burpees30; Burpees; 2026-10-01; 2026-10-30; daily=100");
+        append_challenge(&burpees.unwrap(), &cfg).unwrap();
+
+        let challenges = read_challenges(&cfg).unwrap();
+        assert_eq!(2, challenges.len());
+        assert_eq!(challenge, challenges[0]);
+        assert_eq!(ChallengeTarget::Daily(100), challenges[1].target);
+        assert_eq!(30, challenges[1].days());
+        assert_eq!(
+            "october;Pushups;2026-10-01;2026-10-31;total=3000",
+            format_challenge(&challenge)
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        for line in &[
+            "october;Pushups;2026-10-01;2026-10-31",
+            "october;Pushups;2026-10-01;2026-09-30;total=3000",
+            "october;Pushups;2026-10-01;2026-10-31;weekly=300",
+            "october;Pushups;2026-10-01;2026-10-31;total=lots",
+            "october;Pushups;01.10.2026;2026-10-31;total=3000",
+        ] {
+            assert!(parse_challenge(line).is_err(), "{}", line);
+        }
+
+        let tmp_dir = TempDir::new().unwrap();
+        let cfg = Config::new(tmp_dir.path().to_str().unwrap());
+        let path = tmp_dir.path().join(CHALLENGE_FILE);
+        fs::write(&path, "naday challenges v1\n# comment\nx;y;z\n").unwrap();
+        let error: Error = read_challenges(&cfg).unwrap_err().into();
+        assert_eq!(5, error.exit_code());
+        assert!(error
+            .to_string()
+            .contains(", line 3: Invalid challenge <x;y;z>"));
+    }
+}
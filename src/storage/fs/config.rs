@@ -0,0 +1,324 @@
+use crate::model::Config;
+
+use super::format::{self, FileKind};
+use super::lock;
+use crate::error::Error;
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::NaiveDate;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Read the config file in the data directory and apply all settings found there to the given
+/// config. Settings not present in the file keep their current value.
+pub fn read_config(cfg: &mut Config) -> Result<()> {
+    let path = init_config_file(cfg)?;
+    apply_config_file(&path, cfg)
+}
+
+/// Like `read_config`, but without creating the config file: Without one, the config is kept as
+/// it is.
+pub fn read_config_read_only(cfg: &mut Config) -> Result<()> {
+    let path = Path::new(&cfg.data_dir).join(CONFIG_FILE);
+    if path.exists() {
+        apply_config_file(&path, cfg)?;
+    }
+
+    Ok(())
+}
+
+/// Change a single setting in the config file: An active line for that setting is replaced,
+/// otherwise the setting is appended to the file.
+pub fn write_setting(cfg: &Config, name: &str, value: &str) -> Result<()> {
+    let path = init_config_file(cfg)?;
+
+    let _lock = lock::lock(cfg)?;
+    let contents = fs::read_to_string(&path)
+        .with_context(|| format!("Unable to read config file {:?}", &path))?;
+    let contents = format::upgrade(&contents, FileKind::Config, &path)?;
+
+    let setting = format!("{} = {}", name, value);
+    let mut found = false;
+    let mut result = String::new();
+
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        let is_setting = !trimmed.starts_with('#')
+            && trimmed.split_once('=').map(|(key, _)| key.trim()) == Some(name);
+
+        if is_setting && !found {
+            result.push_str(&setting);
+            found = true;
+        } else if !is_setting {
+            result.push_str(line);
+        } else {
+            continue; // drop duplicate settings, the last one would win anyway
+        }
+        result.push('\n');
+    }
+
+    if !found {
+        result.push_str(&setting);
+        result.push('\n');
+    }
+
+    lock::write_atomic(&path, &result)
+        .with_context(|| format!("Could not write config file {:?}", &path))?;
+
+    Ok(())
+}
+
+//
+// Internals ---------------------------
+//
+
+pub const CONFIG_FILE: &str = "config.txt";
+
+fn init_config_file(cfg: &Config) -> Result<PathBuf> {
+    let mut path = super::init_data_dir(cfg)?;
+
+    path.push(CONFIG_FILE);
+
+    let contents = format!(
+        "\
+{}
+# Settings for the 'naday' tool (https://github.com/creinig/naday).
+# Lines beginning with '#' are comments and are ignored by the tool.
+# The remaining lines have the format 'name = value', encoding is UTF-8.
+# All settings are optional - the commented out values below are the defaults.
+
+# How category names / aliases are matched when logging or reporting:
+#   exact  - only full names and aliases (case insensitive)
+#   prefix - additionally accept unambiguous prefixes, e.g. 'bur' for 'Burpees'
+# category_matching = prefix
+
+# Where activities and categories are stored:
+#   text   - plain text files in this directory
+#   sqlite - a single SQLite database in this directory (only if naday was built with the
+#            'sqlite' feature). Use 'naday migrate --to sqlite|text' to switch with your data.
+# storage = text
+
+# Backups of the data directory are created in the 'backups' subdirectory before all operations
+# rewriting data. With daily_backup = true, a backup is also created once per day.
+# backups_to_keep is the number of automatic backups kept - older ones are deleted.
+# daily_backup = false
+# backups_to_keep = 10
+
+# Time zone whose calendar days activities are counted for in reports, as IANA name
+# (e.g. 'Europe/Berlin'). Set this if you travel, so activities don't move to other days.
+# 'local' is the time zone of the system naday runs on.
+# home_timezone = local
+
+# Hour at which a new day starts, e.g. 4 to count activities until 03:59 for the previous day.
+# day_starts_at = 0
+
+# First day of the week (e.g. monday or sunday). Reports separate weeks before that day.
+# week_starts_on = monday
+
+# Format of dates in reports, see https://docs.rs/chrono/latest/chrono/format/strftime/
+# date_format = %Y-%m-%d
+
+# Pace for reaching the daily goals of categories, used by 'naday remind': Which percentage of
+# the goal should be reached by which time of day. Between these times the percentage grows
+# linearly from the start of the day.
+# pace = 12:00=30,18:00=75,21:00=100
+
+# Shell command run by 'naday remind' when you are behind your goals, e.g.
+# 'termux-notification --title naday --content \"$NADAY_MESSAGE\"'. The reminder text is passed
+# in the environment variable NADAY_MESSAGE.
+# remind_command =
+",
+        FileKind::Config.preamble()
+    );
+    lock::init_file(&path, &contents, cfg)
+        .with_context(|| format!("Could not initialize config file {:?}", &path))?;
+
+    Ok(path)
+}
+
+/// Apply all settings in the given config file
+fn apply_config_file(path: &Path, cfg: &mut Config) -> Result<()> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Unable to read config file {:?}", path))?;
+    let contents = format::upgrade(&contents, FileKind::Config, path)?;
+
+    // the preamble is line 1
+    let lines = contents.lines().skip(1);
+    for (line_no, line) in lines.enumerate().map(|(idx, line)| (idx + 2, line)) {
+        let line = line.trim();
+        if line.starts_with('#') || line.is_empty() {
+            continue;
+        }
+
+        if let Err(error) = apply_setting(line, cfg) {
+            bail!(Error::parse(
+                path.display().to_string(),
+                Some(line_no),
+                format!("Invalid setting <{}>: {:#}", line, error)
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse a single 'name = value' line and apply it to the config
+fn apply_setting(line: &str, cfg: &mut Config) -> Result<()> {
+    let (name, value) = match line.split_once('=') {
+        Some((name, value)) => (name.trim(), value.trim()),
+        None => bail!("Expected 'name = value'"),
+    };
+
+    match name {
+        "category_matching" => cfg.category_matching = value.parse()?,
+        "storage" => cfg.storage_backend = value.parse()?,
+        "daily_backup" => cfg.daily_backup = value.parse()?,
+        "backups_to_keep" => cfg.backups_to_keep = value.parse()?,
+        "day_starts_at" => cfg.day_starts_at = parse_hour(value)?,
+        "week_starts_on" => {
+            cfg.week_starts_on = value
+                .parse()
+                .map_err(|_| anyhow!("Unknown weekday '{}'", value))?
+        }
+        "date_format" => {
+            // time and offset fields can't be formatted for plain dates
+            let mut sample = String::new();
+            if write!(sample, "{}", NaiveDate::from_ymd(2021, 1, 1).format(value)).is_err() {
+                bail!("Invalid date format '{}'", value);
+            }
+            cfg.date_format = value.to_string();
+        }
+        "pace" => cfg.pace = value.parse()?,
+        "remind_command" if value.is_empty() => cfg.remind_command = None,
+        "remind_command" => cfg.remind_command = Some(value.to_string()),
+        "home_timezone" if value.eq_ignore_ascii_case("local") => cfg.home_timezone = None,
+        "home_timezone" => {
+            cfg.home_timezone = Some(value.parse().map_err(|_| {
+                anyhow!(
+                    "Unknown time zone '{}' - use an IANA name like 'Europe/Berlin'",
+                    value
+                )
+            })?)
+        }
+        _ => bail!("Unknown setting '{}'", name),
+    }
+
+    Ok(())
+}
+
+/// Parse an hour of the day, given as '4' or '04:00'
+fn parse_hour(value: &str) -> Result<u32> {
+    let hour = match value.split_once(':') {
+        Some((hour, "00")) => hour,
+        Some(_) => bail!("Only full hours are supported, e.g. '04:00'"),
+        None => value,
+    };
+
+    match hour.parse() {
+        Ok(hour) if hour < 24 => Ok(hour),
+        _ => bail!("Invalid hour '{}' - expected a value from 0 to 23", value),
+    }
+}
+
+//
+// Tests --------------------------------
+//
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::{CategoryMatching, Config, Pace, StorageBackend};
+    use chrono::Weekday;
+    use tempfile::TempDir;
+
+    #[test]
+    fn file_init() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut cfg = Config::new(tmp_dir.path().to_str().unwrap());
+
+        read_config(&mut cfg).unwrap();
+        assert!(tmp_dir.path().join("config.txt").exists());
+        assert_eq!(CategoryMatching::Prefix, cfg.category_matching);
+    }
+
+    #[test]
+    fn write() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut cfg = Config::new(tmp_dir.path().to_str().unwrap());
+
+        write_setting(&cfg, "category_matching", "exact").unwrap();
+        read_config(&mut cfg).unwrap();
+        assert_eq!(CategoryMatching::Exact, cfg.category_matching);
+
+        write_setting(&cfg, "category_matching", "prefix").unwrap();
+        read_config(&mut cfg).unwrap();
+        assert_eq!(CategoryMatching::Prefix, cfg.category_matching);
+
+        let contents = fs::read_to_string(tmp_dir.path().join("config.txt")).unwrap();
+        assert_eq!(
+            1,
+            contents
+                .lines()
+                .filter(|l| l.starts_with("category_matching"))
+                .count()
+        );
+        assert!(contents.contains("# category_matching = prefix"));
+    }
+
+    #[test]
+    fn settings() {
+        let mut cfg = Config::new("/tmp");
+
+        apply_setting("category_matching = exact", &mut cfg).unwrap();
+        assert_eq!(CategoryMatching::Exact, cfg.category_matching);
+
+        apply_setting("category_matching=PREFIX", &mut cfg).unwrap();
+        assert_eq!(CategoryMatching::Prefix, cfg.category_matching);
+
+        apply_setting("storage = text", &mut cfg).unwrap();
+        assert_eq!(StorageBackend::Text, cfg.storage_backend);
+
+        apply_setting("daily_backup = true", &mut cfg).unwrap();
+        assert!(cfg.daily_backup);
+        apply_setting("backups_to_keep = 3", &mut cfg).unwrap();
+        assert_eq!(3, cfg.backups_to_keep);
+
+        apply_setting("home_timezone = America/New_York", &mut cfg).unwrap();
+        assert_eq!(Some(chrono_tz::America::New_York), cfg.home_timezone);
+        apply_setting("home_timezone = local", &mut cfg).unwrap();
+        assert_eq!(None, cfg.home_timezone);
+
+        apply_setting("day_starts_at = 04:00", &mut cfg).unwrap();
+        assert_eq!(4, cfg.day_starts_at);
+        apply_setting("day_starts_at = 5", &mut cfg).unwrap();
+        assert_eq!(5, cfg.day_starts_at);
+
+        apply_setting("week_starts_on = Sunday", &mut cfg).unwrap();
+        assert_eq!(Weekday::Sun, cfg.week_starts_on);
+        apply_setting("date_format = %d.%m.", &mut cfg).unwrap();
+        assert_eq!("%d.%m.", cfg.date_format);
+
+        apply_setting("pace = 20:00=100, 08:00=10", &mut cfg).unwrap();
+        assert_eq!("20:00=100,08:00=10".parse::<Pace>().unwrap(), cfg.pace);
+        apply_setting("remind_command = notify-send \"$NADAY_MESSAGE\"", &mut cfg).unwrap();
+        assert_eq!(
+            Some("notify-send \"$NADAY_MESSAGE\""),
+            cfg.remind_command.as_deref()
+        );
+        apply_setting("remind_command =", &mut cfg).unwrap();
+        assert_eq!(None, cfg.remind_command);
+
+        apply_setting("pace = 18:00=175", &mut cfg).unwrap_err();
+        apply_setting("pace = 6pm=75", &mut cfg).unwrap_err();
+        apply_setting("week_starts_on = someday", &mut cfg).unwrap_err();
+        apply_setting("date_format = %Q", &mut cfg).unwrap_err();
+        apply_setting("date_format = %Y-%m-%d %H:%M", &mut cfg).unwrap_err();
+        apply_setting("date_format = %d.%m. %z", &mut cfg).unwrap_err();
+        apply_setting("day_starts_at = 24", &mut cfg).unwrap_err();
+        apply_setting("day_starts_at = 04:30", &mut cfg).unwrap_err();
+        apply_setting("home_timezone = Mars/Olympus", &mut cfg).unwrap_err();
+        apply_setting("daily_backup = yes", &mut cfg).unwrap_err();
+        apply_setting("category_matching = fuzzy", &mut cfg).unwrap_err();
+        apply_setting("no_such_setting = 1", &mut cfg).unwrap_err();
+        apply_setting("category_matching", &mut cfg).unwrap_err();
+    }
+}
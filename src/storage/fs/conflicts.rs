@@ -0,0 +1,196 @@
+use crate::model::Config;
+
+use super::{activity, backup, category, lock};
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+
+/// Result of merging a single conflict copy
+#[derive(Debug)]
+pub struct MergedConflict {
+    /// File name of the conflict copy
+    pub copy: String,
+    /// File name of the file it was merged into
+    pub target: String,
+    /// Number of merged activities or categories
+    pub merged: usize,
+    /// Entries that could not be merged automatically
+    pub notes: Vec<String>,
+}
+
+/// Merge all sync conflict copies of activity files and the category file into the original
+/// files, and move the copies to the archive directory. The data directory is backed up before.
+pub fn merge_conflicts(cfg: &Config) -> Result<Vec<MergedConflict>> {
+    let dir = super::init_data_dir(cfg)?;
+    let copies = conflict_copies(&dir)?;
+    if copies.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let _lock = lock::lock(cfg)?;
+    backup::create_locked(cfg, "merge-conflicts")?;
+    let archive = dir.join(ARCHIVE_DIR);
+    fs::create_dir_all(&archive)
+        .with_context(|| format!("Could not create directory {:?}", &archive))?;
+
+    let mut results = Vec::new();
+    for (copy, target) in copies {
+        let (merged, notes) = if category::is_category_file(&target) {
+            category::merge_file(cfg, &dir.join(&copy))?
+        } else {
+            (
+                activity::merge_file(&dir.join(&target), &dir.join(&copy))?,
+                Vec::new(),
+            )
+        };
+
+        fs::rename(dir.join(&copy), archive.join(&copy))
+            .with_context(|| format!("Could not move {:?} to {:?}", &copy, &archive))?;
+
+        results.push(MergedConflict {
+            copy,
+            target,
+            merged,
+            notes,
+        });
+    }
+
+    Ok(results)
+}
+
+//
+// Internals ---------------------------
+//
+
+/// Directory (inside the data directory) the merged conflict copies are moved to
+const ARCHIVE_DIR: &str = "sync-conflicts";
+
+lazy_static! {
+    /// Syncthing names conflict copies '<name>.sync-conflict-<date>-<time>-<device id>.<ext>'
+    static ref CONFLICT_PATTERN: Regex =
+        Regex::new(r"^(.+)\.sync-conflict-\d{8}-\d{6}(?:-[0-9A-Z]+)?(\.[^.]+)?$").unwrap();
+}
+
+/// Find the conflict copies of all files naday can merge
+///
+/// # Returns
+/// The file names of all copies and the files they belong to, sorted by name
+fn conflict_copies(dir: &Path) -> Result<Vec<(String, String)>> {
+    let mut copies = Vec::new();
+
+    for entry in fs::read_dir(dir).with_context(|| format!("Could not list {:?}", dir))? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        if let Some(target) = original_name(&name) {
+            if activity::is_activity_file(&target) || category::is_category_file(&target) {
+                copies.push((name, target));
+            }
+        }
+    }
+
+    copies.sort();
+    Ok(copies)
+}
+
+/// The name of the file the given conflict copy belongs to, if it is a conflict copy at all
+fn original_name(file_name: &str) -> Option<String> {
+    CONFLICT_PATTERN.captures(file_name).map(|groups| {
+        format!(
+            "{}{}",
+            &groups[1],
+            groups.get(2).map(|ext| ext.as_str()).unwrap_or("")
+        )
+    })
+}
+
+//
+// Tests ---------------------------------
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Activity;
+    use crate::storage::fs::TextStorage;
+    use crate::storage::Storage;
+    use chrono::{Local, TimeZone};
+    use tempfile::TempDir;
+
+    #[test]
+    fn conflict_names() {
+        assert_eq!(
+            Some("2026-10.txt".to_string()),
+            original_name("2026-10.sync-conflict-20261017-153012-ABCDEF7.txt")
+        );
+        assert_eq!(
+            Some("categories.txt".to_string()),
+            original_name("categories.sync-conflict-20261017-153012-ABCDEF7.txt")
+        );
+        assert_eq!(None, original_name("2026-10.txt"));
+        assert_eq!(None, original_name("2026-10.sync-conflict.txt"));
+    }
+
+    #[test]
+    fn merge() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cfg = Config::new(tmp_dir.path().to_str().unwrap());
+        let dir = tmp_dir.path();
+        let storage = TextStorage::new(&cfg);
+
+        // the conflict copy holds one shared and two new activities, plus a new category
+        let shared = activity(3, 20, "Pushups");
+        storage.store(&shared).unwrap();
+        storage.store(&activity(4, 30, "Situps")).unwrap();
+
+        let copy = "2026-10.sync-conflict-20261005-101010-ABCDEF7.txt";
+        fs::write(
+            dir.join(copy),
+            "naday activities v1\n\
+             2026-10-03 12:00:00;20;Pushups\n\
+             2026-10-04 12:00:00;10;Burpees\n\
+             2026-10-05 12:00:00;12;Lunges\n",
+        )
+        .unwrap();
+
+        storage.read_categories().unwrap(); // initializes the category file
+        let categories = "categories.sync-conflict-20261005-101010-ABCDEF7.txt";
+        let mut contents = fs::read_to_string(dir.join("categories.txt")).unwrap();
+        contents.push_str("Lunges;1;lu\nSquats;1;pu\n");
+        fs::write(dir.join(categories), contents).unwrap();
+
+        let results = merge_conflicts(&cfg).unwrap();
+        assert_eq!(2, results.len());
+
+        // sorted by name: activities first
+        assert_eq!("2026-10.txt", results[0].target);
+        assert_eq!(2, results[0].merged);
+        assert_eq!("categories.txt", results[1].target);
+        assert_eq!(1, results[1].merged);
+        assert_eq!(1, results[1].notes.len(), "{:?}", results[1].notes);
+
+        let activities = storage.read_all().unwrap();
+        assert_eq!(4, activities.len());
+        assert!(storage.read_categories().unwrap().find("lu").is_some());
+
+        assert!(!dir.join(copy).exists());
+        assert!(dir.join(ARCHIVE_DIR).join(copy).exists());
+        assert!(dir.join(ARCHIVE_DIR).join(categories).exists());
+        assert!(merge_conflicts(&cfg).unwrap().is_empty());
+    }
+
+    fn activity(day: u32, reps: u32, category: &str) -> Activity {
+        Activity {
+            timestamp: Local.ymd(2026, 10, day).and_hms(12, 0, 0),
+            reps,
+            category: category.to_string(),
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use crate::model::Config;
+
+use super::{activity, backup, category, lock};
+use crate::error::Error;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The kinds of files in the data directory. Each kind has its own format version, stated in the
+/// preamble (first line) of each file: 'naday <kind> v<version>'.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileKind {
+    Activities,
+    Categories,
+    Config,
+    Challenges,
+    Plan,
+}
+
+/// Converts the contents (without preamble) of a file from one format version to the next
+pub type Upgrade = fn(&str) -> Result<String>;
+
+impl FileKind {
+    /// The preamble of files in the current format version
+    pub fn preamble(self) -> String {
+        preamble(self, self.version())
+    }
+
+    /// The current format version
+    pub fn version(self) -> u32 {
+        self.upgrades().len() as u32 + 1
+    }
+
+    /// All upgrade steps. The step at index `i` converts version `i + 1` to version `i + 2`.
+    fn upgrades(self) -> &'static [Upgrade] {
+        match self {
+            FileKind::Activities => &[activity::add_utc_offsets],
+            FileKind::Categories => &[category::allow_goals],
+            FileKind::Config => &[],
+            FileKind::Challenges => &[],
+            FileKind::Plan => &[],
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            FileKind::Activities => "activities",
+            FileKind::Categories => "categories",
+            FileKind::Config => "config",
+            FileKind::Challenges => "challenges",
+            FileKind::Plan => "plan",
+        }
+    }
+}
+
+/// Convert the given file contents to the current format version (in memory)
+///
+/// # Returns
+/// The contents in the current format version, including the preamble
+pub fn upgrade(contents: &str, kind: FileKind, path: &Path) -> Result<String> {
+    upgrade_with(contents, kind, path, kind.upgrades())
+}
+
+/// Upgrade the given files to the current format versions in place, if necessary. All outdated
+/// files are converted before any of them is written, and the data directory is backed up once
+/// before.
+///
+/// # Returns
+/// The number of upgraded files
+pub fn upgrade_files(files: &[(PathBuf, FileKind)], cfg: &Config) -> Result<usize> {
+    upgrade_files_with(files, cfg, FileKind::upgrades)
+}
+
+//
+// Internals ---------------------------
+//
+
+fn preamble(kind: FileKind, version: u32) -> String {
+    format!("naday {} v{}", kind.name(), version)
+}
+
+/// The format version stated in the given preamble, if it is a valid preamble for that kind
+fn parse_version(preamble: &str, kind: FileKind) -> Option<u32> {
+    preamble
+        .trim()
+        .strip_prefix(&format!("naday {} v", kind.name()))
+        .and_then(|version| version.parse().ok())
+        .filter(|version| *version > 0)
+}
+
+fn upgrade_with(contents: &str, kind: FileKind, path: &Path, steps: &[Upgrade]) -> Result<String> {
+    let file = path.display().to_string();
+    let (first_line, body) = match contents.split_once('\n') {
+        Some((first_line, body)) => (first_line, body),
+        None => (contents, ""),
+    };
+
+    if first_line.trim().is_empty() && body.trim().is_empty() {
+        bail!(Error::parse(file, None, "File seems to be empty"));
+    }
+
+    let version = match parse_version(first_line, kind) {
+        Some(version) => version,
+        None => bail!(Error::parse(
+            file,
+            Some(1),
+            format!(
+                "No valid preamble found - unable to determine {} file format",
+                kind.name()
+            ),
+        )),
+    };
+
+    let current = steps.len() as u32 + 1;
+    if version > current {
+        bail!(Error::parse(
+            file,
+            Some(1),
+            format!(
+                "File format v{} is newer than the formats supported by this version of naday (up to v{})",
+                version, current
+            ),
+        ));
+    }
+    if version == current {
+        return Ok(contents.to_string());
+    }
+
+    let mut body = body.to_string();
+    for (idx, step) in steps.iter().enumerate().skip(version as usize - 1) {
+        body = step(&body).with_context(|| {
+            format!(
+                "Unable to upgrade {} from v{} to v{}",
+                &file,
+                idx + 1,
+                idx + 2
+            )
+        })?;
+    }
+
+    Ok(format!("{}\n{}", preamble(kind, current), body))
+}
+
+fn upgrade_files_with(
+    files: &[(PathBuf, FileKind)],
+    cfg: &Config,
+    steps: fn(FileKind) -> &'static [Upgrade],
+) -> Result<usize> {
+    let outdated = |contents: &str, kind: FileKind| {
+        let first_line = contents.lines().next().unwrap_or("");
+        let current = steps(kind).len() as u32 + 1;
+        matches!(parse_version(first_line, kind), Some(version) if version < current)
+    };
+
+    // cheap check without the lock first
+    let any_outdated = files.iter().any(|(path, kind)| {
+        matches!(fs::read_to_string(path), Ok(contents) if outdated(&contents, *kind))
+    });
+    if !any_outdated {
+        return Ok(0);
+    }
+
+    let _lock = lock::lock(cfg)?;
+    let mut upgraded = Vec::new();
+    for (path, kind) in files {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(error) => {
+                return Err(error).with_context(|| format!("Unable to read file {:?}", path))
+            }
+        };
+        // files upgraded by another process in the meantime are skipped
+        if outdated(&contents, *kind) {
+            upgraded.push((path, upgrade_with(&contents, *kind, path, steps(*kind))?));
+        }
+    }
+
+    if !upgraded.is_empty() {
+        backup::create_locked(cfg, "upgrade")?;
+    }
+    for (path, contents) in &upgraded {
+        lock::write_atomic(path, contents)
+            .with_context(|| format!("Could not write upgraded file {:?}", path))?;
+    }
+
+    Ok(upgraded.len())
+}
+
+//
+// Tests ---------------------------------
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const STEPS: &[Upgrade] = &[
+        |body| Ok(body.replace(';', ",")),
+        |body| Ok(format!("# upgraded\n{}", body)),
+    ];
+
+    #[test]
+    fn versions() {
+        assert_eq!("naday activities v2", FileKind::Activities.preamble());
+        assert_eq!(
+            Some(12),
+            parse_version("naday config v12 ", FileKind::Config)
+        );
+        assert_eq!(
+            None,
+            parse_version("naday config v12", FileKind::Categories)
+        );
+        assert_eq!(None, parse_version("naday config v0", FileKind::Config));
+        assert_eq!(None, parse_version("naday config", FileKind::Config));
+    }
+
+    #[test]
+    fn upgrade_in_memory() {
+        let path = Path::new("x.txt");
+        let kind = FileKind::Activities;
+
+        let upgraded = upgrade_with("naday activities v1\na;b\n", kind, path, STEPS).unwrap();
+        assert_eq!("naday activities v3\n# upgraded\na,b\n", upgraded);
+        let upgraded = upgrade_with("naday activities v2\na;b\n", kind, path, STEPS).unwrap();
+        assert_eq!("naday activities v3\n# upgraded\na;b\n", upgraded);
+        let current = "naday activities v3\na;b\n";
+        assert_eq!(current, upgrade_with(current, kind, path, STEPS).unwrap());
+
+        let error: Error = upgrade_with("naday activities v4\n", kind, path, STEPS)
+            .unwrap_err()
+            .into();
+        assert!(error.to_string().contains("newer"), "{}", error);
+        assert_eq!(5, error.exit_code());
+        assert!(upgrade_with("naday categories v1\n", kind, path, STEPS).is_err());
+        assert!(upgrade_with("", kind, path, STEPS).is_err());
+    }
+
+    #[test]
+    fn upgrade_in_place() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cfg = Config::new(tmp_dir.path().to_str().unwrap());
+        let files: Vec<(PathBuf, FileKind)> = ["2021-01.txt", "2021-02.txt", "2021-03.txt"]
+            .iter()
+            .map(|name| (tmp_dir.path().join(name), FileKind::Activities))
+            .collect();
+        fs::write(&files[0].0, "naday activities v1\na;b\n").unwrap();
+        fs::write(&files[1].0, "naday activities v2\nc;d\n").unwrap();
+        fs::write(&files[2].0, "naday activities v3\ne;f\n").unwrap();
+
+        assert_eq!(2, upgrade_files_with(&files, &cfg, |_| STEPS).unwrap());
+        assert_eq!(
+            "naday activities v3\n# upgraded\na,b\n",
+            fs::read_to_string(&files[0].0).unwrap()
+        );
+        assert_eq!(
+            "naday activities v3\n# upgraded\nc;d\n",
+            fs::read_to_string(&files[1].0).unwrap()
+        );
+        assert_eq!(
+            "naday activities v3\ne;f\n",
+            fs::read_to_string(&files[2].0).unwrap()
+        );
+        assert_eq!(0, upgrade_files_with(&files, &cfg, |_| STEPS).unwrap());
+
+        // one backup for all upgraded files
+        let backups = backup::list(&cfg).unwrap();
+        assert_eq!(1, backups.len());
+        assert_eq!("upgrade", backups[0].reason);
+        let backup_dir = tmp_dir.path().join("backups").join(&backups[0].id);
+        assert_eq!(
+            "naday activities v1\na;b\n",
+            fs::read_to_string(backup_dir.join("2021-01.txt")).unwrap()
+        );
+        assert_eq!(
+            "naday activities v2\nc;d\n",
+            fs::read_to_string(backup_dir.join("2021-02.txt")).unwrap()
+        );
+    }
+
+    #[test]
+    fn upgrade_nothing_on_error() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cfg = Config::new(tmp_dir.path().to_str().unwrap());
+        let files: Vec<(PathBuf, FileKind)> = ["2021-01.txt", "2021-02.txt"]
+            .iter()
+            .map(|name| (tmp_dir.path().join(name), FileKind::Activities))
+            .collect();
+        fs::write(&files[0].0, "naday activities v1\na;b\n").unwrap();
+        fs::write(&files[1].0, "naday activities v1\nc;d\n").unwrap();
+        let failing: fn(FileKind) -> &'static [Upgrade] = |_| {
+            &[|body| {
+                if body.contains('c') {
+                    bail!("Unreadable")
+                }
+                Ok(body.to_string())
+            }]
+        };
+
+        assert!(upgrade_files_with(&files, &cfg, failing).is_err());
+        assert_eq!(
+            "naday activities v1\na;b\n",
+            fs::read_to_string(&files[0].0).unwrap()
+        );
+        assert!(backup::list(&cfg).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,110 @@
+use crate::model::Config;
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Exclusive advisory lock on the data directory, held by every process modifying files in it.
+/// The lock is released when this is dropped.
+pub struct DirLock {
+    _file: File,
+}
+
+/// Block until the lock on the data directory is acquired.
+/// Must not be called while already holding the lock - the second call would block forever.
+pub fn lock(cfg: &Config) -> Result<DirLock> {
+    let path = super::init_data_dir(cfg)?.join(LOCK_FILE);
+
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("Could not open lock file {:?}", &path))?;
+    file.lock()
+        .with_context(|| format!("Could not lock {:?}", &path))?;
+
+    Ok(DirLock { _file: file })
+}
+
+/// Replace the contents of the given file atomically: Readers either see the old or the new
+/// contents, never a partially written file.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let tmp_path = tmp_path_for(path);
+
+    let mut file = File::create(&tmp_path)
+        .with_context(|| format!("Could not create temporary file {:?}", &tmp_path))?;
+    file.write_all(contents.as_ref())
+        .and_then(|_| file.sync_all())
+        .with_context(|| format!("Could not write temporary file {:?}", &tmp_path))?;
+
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("Could not replace {:?} with {:?}", path, &tmp_path))?;
+
+    Ok(())
+}
+
+/// Create the given file with the given contents, unless it already exists. Safe against
+/// concurrent initialization by multiple processes.
+pub fn init_file(path: &Path, contents: &str, cfg: &Config) -> Result<()> {
+    if path.exists() {
+        return Ok(());
+    }
+
+    let _lock = lock(cfg)?;
+    if !path.exists() {
+        write_atomic(path, contents)?;
+    }
+
+    Ok(())
+}
+
+//
+// Internals ---------------------------
+//
+
+const LOCK_FILE: &str = ".naday.lock";
+
+/// Temporary file in the same directory (and thus on the same filesystem) as the given one
+fn tmp_path_for(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+//
+// Tests ---------------------------------
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+    use tempfile::TempDir;
+
+    #[test]
+    fn concurrent_init() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cfg = Config::new(tmp_dir.path().to_str().unwrap());
+        let path = tmp_dir.path().join("file.txt");
+        let barrier = Arc::new(Barrier::new(8));
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let (cfg, path, barrier) = (cfg.clone(), path.clone(), barrier.clone());
+                thread::spawn(move || {
+                    barrier.wait();
+                    init_file(&path, "preamble\n", &cfg).unwrap();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!("preamble\n", fs::read_to_string(&path).unwrap());
+        assert!(!tmp_path_for(&path).exists());
+    }
+}
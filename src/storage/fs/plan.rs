@@ -0,0 +1,385 @@
+use crate::model::{Config, Increase, Plan, PlanSchedule, Schedule};
+
+use super::format::{self, FileKind};
+use super::lock;
+use crate::error::Error;
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Read the training plan in the data directory, if there is one
+pub fn read_plan(cfg: &Config) -> Result<Option<Plan>> {
+    let path = plan_path(cfg);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let contents = fs::read_to_string(&path)
+        .with_context(|| format!("Unable to read plan file {:?}", &path))?;
+    let contents = format::upgrade(&contents, FileKind::Plan, &path)?;
+
+    // skip the preamble
+    Ok(Some(parse_plan(&contents, 1, &path)?))
+}
+
+/// Read a training plan from any file. The preamble is optional, so plans can be written by hand.
+pub fn read_plan_file(path: &Path) -> Result<Plan> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Unable to read plan file {:?}", path))?;
+
+    if contents.starts_with("naday plan ") {
+        let contents = format::upgrade(&contents, FileKind::Plan, path)?;
+        parse_plan(&contents, 1, path)
+    } else {
+        parse_plan(&contents, 0, path)
+    }
+}
+
+/// Replace the training plan in the data directory, or remove it
+pub fn write_plan(cfg: &Config, plan: Option<&Plan>) -> Result<()> {
+    let path = super::init_data_dir(cfg)?.join(PLAN_FILE);
+    let _lock = lock::lock(cfg)?;
+
+    let plan = match plan {
+        Some(plan) => plan,
+        None => {
+            if path.exists() {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Could not remove plan file {:?}", &path))?;
+            }
+            return Ok(());
+        }
+    };
+
+    let mut contents = format!(
+        "{}\n{}start = {}\n",
+        FileKind::Plan.preamble(),
+        PLAN_FILE_HEADER,
+        plan.start.format(DATE_FORMAT)
+    );
+    for schedule in &plan.schedules {
+        contents.push_str(&format_schedule(schedule));
+        contents.push('\n');
+    }
+
+    lock::write_atomic(&path, &contents)
+        .with_context(|| format!("Could not write plan file {:?}", &path))?;
+    Ok(())
+}
+
+//
+// Internals ---------------------------
+//
+
+pub const PLAN_FILE: &str = "plan.txt";
+const DATE_FORMAT: &str = "%Y-%m-%d";
+/// Limits keeping targets and dates computable: About ten years of at most a million repetitions
+const MAX_WEEKS: u32 = 520;
+const MAX_TARGET: u32 = 1_000_000;
+const MAX_PERCENT: u32 = 100;
+
+const PLAN_FILE_HEADER: &str = "\
+# Training plan for the 'naday' tool (https://github.com/creinig/naday).
+# Lines beginning with '#' are comments and are ignored by the tool.
+# 'start = <YYYY-MM-DD>' is the first day of the plan. The remaining lines define the daily
+# targets of one category each, separated by ';':
+#   <category>;from=<reps>;weekly=<+N or +N%>;weeks=<N> - a target raised each week
+#   <category>;days=<reps>,<reps>,...                  - one target per day, 0 for rest days
+";
+
+fn plan_path(cfg: &Config) -> PathBuf {
+    Path::new(&cfg.data_dir).join(PLAN_FILE)
+}
+
+/// Parse the contents of a plan file, skipping the given number of lines (the preamble)
+fn parse_plan(contents: &str, skip: usize, path: &Path) -> Result<Plan> {
+    let mut start = None;
+    let mut schedules = Vec::new();
+
+    for (idx, line) in contents.lines().enumerate().skip(skip) {
+        let line = line.trim();
+        if line.starts_with('#') || line.is_empty() {
+            continue;
+        }
+
+        let parsed = match line.split_once('=') {
+            Some((key, value)) if key.trim() == "start" => {
+                NaiveDate::parse_from_str(value.trim(), DATE_FORMAT)
+                    .map(|date| start = Some(date))
+                    .with_context(|| format!("Unable to parse start date <{}>", value.trim()))
+            }
+            _ => parse_schedule(line).map(|schedule| schedules.push(schedule)),
+        };
+        if let Err(error) = parsed {
+            bail!(Error::parse(
+                path.display().to_string(),
+                Some(idx + 1),
+                format!("Invalid plan line <{}>: {:#}", line, error)
+            ));
+        }
+    }
+
+    let file = path.display().to_string();
+    match start {
+        None => bail!(Error::parse(file, None, "No 'start = <YYYY-MM-DD>' found")),
+        Some(_) if schedules.is_empty() => {
+            bail!(Error::parse(
+                file,
+                None,
+                "No targets for any category found"
+            ))
+        }
+        Some(start) => Ok(Plan { start, schedules }),
+    }
+}
+
+fn parse_schedule(line: &str) -> Result<PlanSchedule> {
+    let mut parts = line.split(';').map(|part| part.trim());
+    let category = match parts.next() {
+        Some(category) if !category.is_empty() && !category.contains('=') => category,
+        _ => bail!("No category found"),
+    };
+
+    let mut attributes = std::collections::HashMap::new();
+    for part in parts {
+        match part.split_once('=') {
+            Some((key, value)) => attributes.insert(key.trim(), value.trim()),
+            None => bail!("Expected 'name=value' instead of <{}>", part),
+        };
+    }
+
+    let number = |name: &str, max: u32| -> Result<u32> {
+        let number = match attributes.get(name) {
+            Some(value) => value
+                .parse()
+                .with_context(|| format!("Unable to parse {} <{}>", name, value))?,
+            None => bail!("No '{}' found", name),
+        };
+        if number > max {
+            bail!("{} must not be larger than {}", name, max);
+        }
+        Ok(number)
+    };
+
+    let schedule = match attributes.get("days") {
+        Some(days) => Schedule::Days(
+            days.split(',')
+                .map(|target| {
+                    target
+                        .trim()
+                        .parse()
+                        .with_context(|| format!("Unable to parse daily target <{}>", target))
+                })
+                .map(|target| match target {
+                    Ok(target) if target > MAX_TARGET => {
+                        bail!("Daily targets must not be larger than {}", MAX_TARGET)
+                    }
+                    target => target,
+                })
+                .collect::<Result<Vec<u32>>>()?,
+        ),
+        None => {
+            let increase = match attributes.get("weekly") {
+                Some(weekly) => parse_increase(weekly)?,
+                None => bail!("Expected either 'days' or 'from', 'weekly' and 'weeks'"),
+            };
+            Schedule::Weekly {
+                initial: number("from", MAX_TARGET)?,
+                increase,
+                weeks: number("weeks", MAX_WEEKS)?,
+            }
+        }
+    };
+    match schedule.days() {
+        0 => bail!("The schedule has no days"),
+        days if days > MAX_WEEKS * 7 => {
+            bail!(
+                "The schedule must not last longer than {} days",
+                MAX_WEEKS * 7
+            )
+        }
+        _ => {}
+    }
+
+    Ok(PlanSchedule {
+        category: category.to_string(),
+        schedule,
+    })
+}
+
+/// Parse a weekly increase like '+10%' or '+5'
+fn parse_increase(value: &str) -> Result<Increase> {
+    let value = value.trim_start_matches('+');
+    let increase = match value.strip_suffix('%') {
+        Some(percent) => percent.trim().parse().map(Increase::Percent),
+        None => value.parse().map(Increase::Reps),
+    };
+
+    match increase.with_context(|| format!("Unable to parse weekly increase <{}>", value))? {
+        Increase::Percent(percent) if percent > MAX_PERCENT => bail!(
+            "The weekly increase must not be larger than {}%",
+            MAX_PERCENT
+        ),
+        Increase::Reps(reps) if reps > MAX_TARGET => {
+            bail!("The weekly increase must not be larger than {}", MAX_TARGET)
+        }
+        increase => Ok(increase),
+    }
+}
+
+fn format_schedule(schedule: &PlanSchedule) -> String {
+    match &schedule.schedule {
+        Schedule::Weekly {
+            initial,
+            increase,
+            weeks,
+        } => {
+            let increase = match increase {
+                Increase::Percent(percent) => format!("+{}%", percent),
+                Increase::Reps(reps) => format!("+{}", reps),
+            };
+            format!(
+                "{};from={};weekly={};weeks={}",
+                schedule.category, initial, increase, weeks
+            )
+        }
+        Schedule::Days(targets) => format!(
+            "{};days={}",
+            schedule.category,
+            targets
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+    }
+}
+
+//
+// Tests --------------------------------
+//
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn read_and_write() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cfg = Config::new(tmp_dir.path().to_str().unwrap());
+        assert_eq!(None, read_plan(&cfg).unwrap());
+
+        let source = tmp_dir.path().join("hundred.txt");
+        fs::write(
+            &source,
+            "# my plan\nstart = 2026-10-01\npu; from=50; weekly=+10%; weeks=6\nSquats;days=20,0,25\n",
+        )
+        .unwrap();
+        let plan = read_plan_file(&source).unwrap();
+        assert_eq!(NaiveDate::from_ymd(2026, 10, 1), plan.start);
+        assert_eq!(
+            Schedule::Weekly {
+                initial: 50,
+                increase: Increase::Percent(10),
+                weeks: 6
+            },
+            plan.schedules[0].schedule
+        );
+        assert_eq!(Schedule::Days(vec![20, 0, 25]), plan.schedules[1].schedule);
+
+        write_plan(&cfg, Some(&plan)).unwrap();
+        assert_eq!(Some(plan.clone()), read_plan(&cfg).unwrap());
+        let contents = fs::read_to_string(plan_path(&cfg)).unwrap();
+        assert!(contents.starts_with("naday plan v1\n"));
+        assert!(contents.contains("\npu;from=50;weekly=+10%;weeks=6\nSquats;days=20,0,25\n"));
+        assert_eq!(plan, read_plan_file(&plan_path(&cfg)).unwrap());
+
+        write_plan(&cfg, None).unwrap();
+        assert_eq!(None, read_plan(&cfg).unwrap());
+    }
+
+    #[test]
+    fn invalid() {
+        for line in &[
+            "Pushups",
+            "Pushups;from=50;weeks=6",
+            "Pushups;from=50;weekly=10x;weeks=6",
+            "Pushups;from=fifty;weekly=+5;weeks=6",
+            "Pushups;from=50;weekly=+5;weeks=0",
+            "Pushups;days=10,,20",
+            "from=50;weekly=+5;weeks=6",
+            "Pushups;from=50;weekly=+5;weeks=1000000000",
+            "Pushups;from=50;weekly=+5;weeks=521",
+            "Pushups;from=2000000;weekly=+5;weeks=6",
+            "Pushups;from=50;weekly=+101%;weeks=6",
+            "Pushups;from=50;weekly=+2000000;weeks=6",
+            "Pushups;days=10,2000000",
+        ] {
+            assert!(parse_schedule(line).is_err(), "{}", line);
+        }
+        let too_long = format!("Pushups;days={}", vec!["10"; 3641].join(","));
+        assert!(parse_schedule(&too_long).is_err());
+        assert!(parse_schedule("Pushups;from=1000000;weekly=+100%;weeks=520").is_ok());
+        assert_eq!(
+            Increase::Reps(5),
+            parse_schedule("Pushups;from=50;weekly=5;weeks=6")
+                .map(|s| match s.schedule {
+                    Schedule::Weekly { increase, .. } => increase,
+                    _ => panic!("Unexpected schedule"),
+                })
+                .unwrap()
+        );
+
+        let tmp_dir = TempDir::new().unwrap();
+        let path = tmp_dir.path().join("plan.txt");
+        for (contents, msg) in &[
+            ("Pushups;days=10\n", "No 'start"),
+            ("start = 2026-10-01\n", "No targets"),
+            (
+                "start = 1.10.2026\nPushups;days=10\n",
+                "line 1: Invalid plan line",
+            ),
+        ] {
+            fs::write(&path, contents).unwrap();
+            let error: Error = read_plan_file(&path).unwrap_err().into();
+            assert_eq!(5, error.exit_code());
+            assert!(error.to_string().contains(msg), "{}", error);
+        }
+    }
+
+    #[test]
+    fn huge_targets() {
+        let plan = Plan {
+            start: NaiveDate::from_ymd(2021, 3, 1),
+            schedules: vec![
+                PlanSchedule {
+                    category: "Pushups".to_string(),
+                    schedule: Schedule::Weekly {
+                        initial: u32::MAX - 1,
+                        increase: Increase::Reps(u32::MAX),
+                        weeks: u32::MAX,
+                    },
+                },
+                PlanSchedule {
+                    category: "Squats".to_string(),
+                    schedule: Schedule::Weekly {
+                        initial: 1_000_000,
+                        increase: Increase::Percent(100),
+                        weeks: 520,
+                    },
+                },
+            ],
+        };
+
+        assert_eq!(chrono::naive::MAX_DATE, plan.end());
+        let late = NaiveDate::from_ymd(2030, 3, 1);
+        assert_eq!(
+            vec![
+                ("Pushups".to_string(), u32::MAX),
+                ("Squats".to_string(), u32::MAX)
+            ],
+            plan.targets_on(late)
+        );
+    }
+}
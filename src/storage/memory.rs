@@ -0,0 +1,82 @@
+use super::Storage;
+use crate::error::Error;
+use crate::model::{Activity, Category, CategoryLookup};
+
+use chrono::{Date, Local};
+use std::cell::RefCell;
+
+/// Storage backend keeping everything in memory, for testing code that depends on storage
+pub struct MemoryStorage {
+    activities: RefCell<Vec<Activity>>,
+    categories: RefCell<Vec<Category>>,
+}
+
+impl MemoryStorage {
+    pub fn new(categories: Vec<Category>) -> MemoryStorage {
+        MemoryStorage {
+            activities: RefCell::new(Vec::new()),
+            categories: RefCell::new(categories),
+        }
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn store(&self, activity: &Activity) -> Result<(), Error> {
+        self.activities.borrow_mut().push(activity.clone());
+        Ok(())
+    }
+
+    fn read_days(&self, start: &Date<Local>, end: &Date<Local>) -> Result<Vec<Activity>, Error> {
+        Ok(self
+            .activities
+            .borrow()
+            .iter()
+            .filter(|a| &a.timestamp.date() >= start && &a.timestamp.date() <= end)
+            .cloned()
+            .collect())
+    }
+
+    fn read_all(&self) -> Result<Vec<Activity>, Error> {
+        let mut activities = self.activities.borrow().clone();
+        activities.sort_by_key(|a| a.timestamp);
+        Ok(activities)
+    }
+
+    fn read_categories(&self) -> Result<CategoryLookup, Error> {
+        let mut lookup = CategoryLookup::new();
+        for category in self.categories.borrow().iter() {
+            lookup.add(category.clone())?;
+        }
+
+        Ok(lookup)
+    }
+
+    fn add_category(&self, category: &Category) -> Result<(), Error> {
+        self.categories.borrow_mut().push(category.clone());
+        Ok(())
+    }
+
+    fn write_categories(&self, categories: &[Category]) -> Result<(), Error> {
+        *self.categories.borrow_mut() = categories.to_vec();
+        Ok(())
+    }
+
+    fn delete(&self, activity: &Activity) -> Result<(), Error> {
+        let mut activities = self.activities.borrow_mut();
+        match activities.iter().position(|a| a == activity) {
+            Some(idx) => {
+                activities.remove(idx);
+                Ok(())
+            }
+            None => Err(Error::conflict(format!(
+                "Activity {:?} not found",
+                activity
+            ))),
+        }
+    }
+
+    fn update(&self, old: &Activity, new: &Activity) -> Result<(), Error> {
+        self.delete(old)?;
+        self.store(new)
+    }
+}
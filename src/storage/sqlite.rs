@@ -0,0 +1,498 @@
+use super::Storage;
+use crate::error::Error;
+use crate::model::{Activity, Category, CategoryLookup, Config};
+
+use anyhow::{bail, Context, Result};
+use chrono::prelude::*;
+use rusqlite::{params, Connection, OpenFlags};
+use std::path::PathBuf;
+
+/// Storage backend keeping activities and categories in a single SQLite database in the data
+/// directory
+pub struct SqliteStorage {
+    connection: Connection,
+    config: Config,
+}
+
+impl SqliteStorage {
+    /// Open the database in the configured data directory, creating and initializing it if
+    /// necessary
+    pub fn open(config: &Config) -> Result<SqliteStorage> {
+        let path = PathBuf::from(&config.data_dir);
+        std::fs::create_dir_all(&path)
+            .with_context(|| format!("Could not create base directory {:?}", &path))?;
+
+        let path = path.join(DATABASE_FILE);
+        let connection = Connection::open(&path)
+            .with_context(|| format!("Unable to open database {:?}", &path))?;
+        // concurrent writers wait for each other instead of failing
+        connection.busy_timeout(std::time::Duration::from_secs(10))?;
+
+        let storage = SqliteStorage {
+            connection,
+            config: config.clone(),
+        };
+        storage
+            .init_schema()
+            .with_context(|| format!("Unable to initialize database {:?}", &path))?;
+
+        Ok(storage)
+    }
+
+    /// Open the existing database in the configured data directory for reading only, e.g. the
+    /// one of a teammate. Nothing is created or upgraded.
+    pub fn open_read_only(config: &Config) -> Result<SqliteStorage> {
+        let path = PathBuf::from(&config.data_dir).join(DATABASE_FILE);
+        let connection = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .with_context(|| format!("Unable to open database {:?}", &path))?;
+
+        let version: u32 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version != SCHEMA_VERSION {
+            bail!(
+                "Database {:?} has schema version {}, expected {} - run naday on it to upgrade it",
+                &path,
+                version,
+                SCHEMA_VERSION
+            );
+        }
+
+        Ok(SqliteStorage {
+            connection,
+            config: config.clone(),
+        })
+    }
+
+    /// Remove the database in the configured data directory, e.g. after migrating to another
+    /// backend. The database must not be open anymore.
+    pub fn remove(config: &Config) -> Result<()> {
+        let path = PathBuf::from(&config.data_dir).join(DATABASE_FILE);
+        if path.exists() {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Could not remove database {:?}", &path))?;
+        }
+
+        Ok(())
+    }
+
+    fn init_schema(&self) -> Result<()> {
+        let version: u32 = self
+            .connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        match version {
+            0 => {
+                self.connection.execute_batch(SCHEMA_V1)?;
+                self.connection.execute_batch(SCHEMA_V2)?;
+                write_categories(&self.connection, &super::fs::default_categories())?;
+            }
+            1 => self.connection.execute_batch(SCHEMA_V2)?,
+            SCHEMA_VERSION => return Ok(()),
+            _ => bail!("Unsupported database schema version {}", version),
+        }
+
+        self.connection
+            .execute_batch(&format!("PRAGMA user_version = {}", SCHEMA_VERSION))?;
+
+        Ok(())
+    }
+
+    fn query_activities(
+        &self,
+        sql: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> Result<Vec<Activity>> {
+        let mut statement = self.connection.prepare(sql)?;
+        let rows = statement.query_map(params, |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, u32>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+
+        let mut activities = Vec::new();
+        for row in rows {
+            let (timestamp, reps, category) = row?;
+            activities.push(Activity {
+                timestamp: str2ts(&timestamp)?,
+                reps,
+                category,
+            });
+        }
+
+        Ok(activities)
+    }
+
+    fn load_categories(&self) -> Result<CategoryLookup> {
+        let mut weight_changes = self
+            .connection
+            .prepare("SELECT since, weight FROM category_weights WHERE category = ?1")?;
+        let mut statement = self.connection.prepare(
+            "SELECT name, weight, aliases, archived, goal FROM categories ORDER BY position",
+        )?;
+        let mut rows = statement.query([])?;
+        let mut lookup = CategoryLookup::new();
+
+        while let Some(row) = rows.next()? {
+            let name: String = row.get(0)?;
+            let aliases: String = row.get(2)?;
+            let aliases: Vec<&str> = aliases.split(';').filter(|a| !a.is_empty()).collect();
+
+            let mut category = Category::new(&name, row.get(1)?, aliases);
+            category.archived = row.get(3)?;
+            category.goal = row.get(4)?;
+
+            let mut changes = weight_changes.query(params![&name])?;
+            while let Some(change) = changes.next()? {
+                let since: String = change.get(0)?;
+                let since = NaiveDate::parse_from_str(&since, DAY_FORMAT)
+                    .with_context(|| format!("Invalid weight change date <{}>", since))?;
+                category.change_weight(since, change.get(1)?);
+            }
+
+            lookup.add(category)?;
+        }
+
+        Ok(lookup)
+    }
+
+    /// Insert all given activities in a single transaction
+    fn insert_activities(&self, activities: &[Activity]) -> Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        {
+            let mut statement = transaction.prepare(
+                "INSERT INTO activities (timestamp, day, reps, category) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for activity in activities {
+                statement.execute(params![
+                    ts2str(&activity.timestamp),
+                    day2str(&self.config.day_of(&activity.timestamp)),
+                    activity.reps,
+                    activity.category
+                ])?;
+            }
+        }
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn insert_new_category(&self, category: &Category) -> Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        insert_category(&transaction, category)?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn delete_activity(&self, activity: &Activity) -> Result<()> {
+        let deleted = self.connection.execute(
+            "DELETE FROM activities WHERE id = (SELECT id FROM activities \
+             WHERE timestamp = ?1 AND reps = ?2 AND category = ?3 ORDER BY id LIMIT 1)",
+            params![
+                ts2str(&activity.timestamp),
+                activity.reps,
+                activity.category
+            ],
+        )?;
+
+        if deleted == 0 {
+            bail!(Error::conflict(format!(
+                "Activity {:?} not found",
+                activity
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn update_activity(&self, old: &Activity, new: &Activity) -> Result<()> {
+        let updated = self.connection.execute(
+            "UPDATE activities SET timestamp = ?4, day = ?5, reps = ?6, category = ?7 \
+             WHERE id = (SELECT id FROM activities \
+             WHERE timestamp = ?1 AND reps = ?2 AND category = ?3 ORDER BY id LIMIT 1)",
+            params![
+                ts2str(&old.timestamp),
+                old.reps,
+                old.category,
+                ts2str(&new.timestamp),
+                day2str(&self.config.day_of(&new.timestamp)),
+                new.reps,
+                new.category
+            ],
+        )?;
+
+        if updated == 0 {
+            bail!(Error::conflict(format!("Activity {:?} not found", old)));
+        }
+
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn store(&self, activity: &Activity) -> Result<(), Error> {
+        self.insert_activities(std::slice::from_ref(activity))
+            .map_err(Error::from)
+    }
+
+    fn store_all(&self, activities: &[Activity]) -> Result<(), Error> {
+        self.insert_activities(activities).map_err(Error::from)
+    }
+
+    fn read_days(&self, start: &Date<Local>, end: &Date<Local>) -> Result<Vec<Activity>, Error> {
+        // the stored day may be off by one if the home time zone was changed since
+        let activities = self
+            .query_activities(
+                "SELECT timestamp, reps, category FROM activities \
+                 WHERE day BETWEEN ?1 AND ?2 ORDER BY id",
+                &[&day2str(&start.pred()), &day2str(&end.succ())],
+            )
+            .map_err(Error::from)?;
+
+        Ok(activities
+            .into_iter()
+            .filter(|a| {
+                let day = self.config.day_of(&a.timestamp);
+                &day >= start && &day <= end
+            })
+            .collect())
+    }
+
+    fn read_all(&self) -> Result<Vec<Activity>, Error> {
+        let mut activities = self
+            .query_activities(
+                "SELECT timestamp, reps, category FROM activities ORDER BY id",
+                &[],
+            )
+            .map_err(Error::from)?;
+
+        activities.sort_by_key(|a| a.timestamp);
+        Ok(activities)
+    }
+
+    fn read_categories(&self) -> Result<CategoryLookup, Error> {
+        self.load_categories().map_err(Error::from)
+    }
+
+    fn add_category(&self, category: &Category) -> Result<(), Error> {
+        self.insert_new_category(category).map_err(Error::from)
+    }
+
+    fn write_categories(&self, categories: &[Category]) -> Result<(), Error> {
+        write_categories(&self.connection, categories).map_err(Error::from)
+    }
+
+    fn delete(&self, activity: &Activity) -> Result<(), Error> {
+        self.delete_activity(activity).map_err(Error::from)
+    }
+
+    fn update(&self, old: &Activity, new: &Activity) -> Result<(), Error> {
+        self.update_activity(old, new).map_err(Error::from)
+    }
+}
+
+//
+// Internals ------------------------------
+//
+
+const DATABASE_FILE: &str = "naday.sqlite";
+const DAY_FORMAT: &str = "%Y-%m-%d";
+const SCHEMA_VERSION: u32 = 2;
+const SCHEMA_V1: &str = "
+CREATE TABLE activities (
+    id INTEGER PRIMARY KEY,
+    timestamp TEXT NOT NULL,
+    day TEXT NOT NULL,
+    reps INTEGER NOT NULL,
+    category TEXT NOT NULL
+);
+CREATE INDEX activities_day ON activities (day);
+CREATE INDEX activities_category ON activities (category);
+
+CREATE TABLE categories (
+    position INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    weight REAL NOT NULL,
+    aliases TEXT NOT NULL,
+    archived INTEGER NOT NULL
+);
+
+CREATE TABLE category_weights (
+    category TEXT NOT NULL REFERENCES categories (name) ON DELETE CASCADE,
+    since TEXT NOT NULL,
+    weight REAL NOT NULL
+);
+";
+/// Changes from schema version 1 to 2
+const SCHEMA_V2: &str = "
+ALTER TABLE categories ADD COLUMN goal INTEGER;
+";
+
+/// Replace all categories with the given ones (in a single transaction)
+fn write_categories(connection: &Connection, categories: &[Category]) -> Result<()> {
+    let transaction = connection.unchecked_transaction()?;
+    transaction.execute("DELETE FROM category_weights", [])?;
+    transaction.execute("DELETE FROM categories", [])?;
+
+    for category in categories {
+        insert_category(&transaction, category)?;
+    }
+
+    transaction.commit()?;
+    Ok(())
+}
+
+fn insert_category(connection: &Connection, category: &Category) -> Result<()> {
+    connection
+        .execute(
+            "INSERT INTO categories (name, weight, aliases, archived, goal) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                category.name,
+                category.weight,
+                category.aliases.join(";"),
+                category.archived,
+                category.goal
+            ],
+        )
+        .with_context(|| format!("Unable to store category {}", category.name))?;
+
+    for (since, weight) in &category.weight_changes {
+        connection.execute(
+            "INSERT INTO category_weights (category, since, weight) VALUES (?1, ?2, ?3)",
+            params![category.name, since.format(DAY_FORMAT).to_string(), weight],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// convert activity timestamp to string (RFC 3339, with UTC offset)
+fn ts2str(timestamp: &DateTime<Local>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+/// parse string as activity timestamp
+fn str2ts(raw: &str) -> Result<DateTime<Local>> {
+    let ts = DateTime::parse_from_rfc3339(raw)
+        .with_context(|| format!("Unable to parse activity timestamp <{}>", raw))?;
+    Ok(ts.with_timezone(&Local))
+}
+
+fn day2str(day: &Date<Local>) -> String {
+    day.format(DAY_FORMAT).to_string()
+}
+
+//
+// Tests ---------------------------------
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn activity_roundtrip() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let storage = SqliteStorage::open(&Config::new(tmp_dir.path().to_str().unwrap()))?;
+
+        let first = activity("2020-12-13T14:34:53+01:00", 13, "Burpees");
+        let second = activity("2020-12-14T10:00:00+01:00", 20, "Situps");
+        let third = activity("2021-01-02T10:00:00+01:00", 5, "Pushups");
+        for a in &[&third, &first, &second] {
+            storage.store(a).unwrap();
+        }
+
+        let start = first.timestamp.date();
+        let end = second.timestamp.date();
+        assert_eq!(
+            vec![first.clone(), second.clone()],
+            storage.read_days(&start, &end).unwrap()
+        );
+        assert_eq!(
+            vec![first.clone(), second.clone(), third.clone()],
+            storage.read_all().unwrap()
+        );
+
+        let updated = activity("2020-12-14T11:00:00+01:00", 21, "Situps");
+        storage.update(&second, &updated).unwrap();
+        storage.delete(&first).unwrap();
+        storage.delete(&first).unwrap_err();
+        assert_eq!(vec![updated, third], storage.read_all().unwrap());
+
+        // reopening keeps the data
+        let storage = SqliteStorage::open(&Config::new(tmp_dir.path().to_str().unwrap()))?;
+        assert_eq!(2, storage.read_all().unwrap().len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn categories() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let storage = SqliteStorage::open(&Config::new(tmp_dir.path().to_str().unwrap()))?;
+
+        // initialized with the same defaults as the text backend
+        let lookup = storage.read_categories().unwrap();
+        assert_eq!(6, lookup.iter().count());
+        assert_eq!("Pushups", lookup.iter().next().unwrap().name);
+
+        let mut burpees = Category::new("Burpees", 1.5, vec!["bu", "oof"]);
+        burpees.change_weight(NaiveDate::from_ymd(2021, 1, 1), 1.2);
+        burpees.goal = Some(50);
+        let mut lunges = Category::new("Lunges", 1.0, Vec::<String>::new());
+        lunges.archived = true;
+        storage.write_categories(&[burpees, lunges]).unwrap();
+        storage
+            .add_category(&Category::new("Squats", 1.0, vec!["sq"]))
+            .unwrap();
+
+        let lookup = storage.read_categories().unwrap();
+        let categories: Vec<String> = lookup.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            vec![
+                "Category (Burpees, 1.5, [\"bu\", \"oof\"], 1.2 since 2021-01-01, goal 50)",
+                "Category (Lunges, 1, [], archived)",
+                "Category (Squats, 1, [\"sq\"])",
+            ],
+            categories
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn schema_upgrade() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let connection = Connection::open(tmp_dir.path().join(DATABASE_FILE))?;
+        connection.execute_batch(SCHEMA_V1)?;
+        connection.execute_batch("PRAGMA user_version = 1")?;
+        drop(connection);
+
+        let storage = SqliteStorage::open(&Config::new(tmp_dir.path().to_str().unwrap()))?;
+        let mut squats = Category::new("Squats", 1.0, vec!["sq"]);
+        squats.goal = Some(100);
+        storage.add_category(&squats).unwrap();
+        assert_eq!(
+            Some(100),
+            storage
+                .read_categories()
+                .unwrap()
+                .get("Squats")
+                .unwrap()
+                .goal
+        );
+
+        Ok(())
+    }
+
+    fn activity(timestamp: &str, reps: u32, category: &str) -> Activity {
+        Activity {
+            timestamp: str2ts(timestamp).unwrap(),
+            reps,
+            category: category.to_string(),
+        }
+    }
+}